@@ -0,0 +1,19 @@
+#![no_std]
+
+use core::num::NonZeroU64;
+use wordmap::map::HashMapNZ64;
+use wordmap::mixer::Mixer;
+use wordmap::set::HashSetNZ64;
+
+pub fn smoke() -> u64 {
+  let mut t = HashMapNZ64::new_with_mixer(Mixer::new([1, 2]));
+  let mut s = HashSetNZ64::new_with_mixer(Mixer::new([3, 4]));
+
+  for i in 1 ..= 100 {
+    let k = NonZeroU64::new(i).unwrap();
+    let _ = t.insert(k, i);
+    let _ = s.insert(k);
+  }
+
+  t.values().sum::<u64>() + s.len() as u64
+}
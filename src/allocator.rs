@@ -0,0 +1,112 @@
+//! This module provides the allocator interface used for a map's table.
+//!
+//! With the nightly `allocator_api` feature, these are the standard library's
+//! `Allocator`, `AllocError`, and `Global`, so a map can be placed in any
+//! allocator. Otherwise, they are stand-ins providing just the parts of that
+//! interface that the map uses.
+
+#[cfg(feature = "allocator_api")]
+pub use core::alloc::AllocError;
+
+#[cfg(feature = "allocator_api")]
+pub use core::alloc::Allocator;
+
+#[cfg(feature = "allocator_api")]
+pub use crate::prelude::alloc::alloc::Global;
+
+#[cfg(not(feature = "allocator_api"))]
+pub use stable::*;
+
+#[cfg(not(feature = "allocator_api"))]
+mod stable {
+  use crate::prelude::*;
+
+  /// The error returned when an allocator fails to allocate memory.
+
+  #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+  pub struct AllocError;
+
+  /// An allocator, following the interface of the unstable
+  /// `core::alloc::Allocator`.
+  ///
+  /// # Safety
+  ///
+  /// Memory returned by `allocate` must remain valid until it is passed to
+  /// `deallocate`, including across moves of the allocator.
+
+  pub unsafe trait Allocator {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError>;
+
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+      let p = self.allocate(layout)?;
+      unsafe { ptr::write_bytes(p.as_ptr() as *mut u8, 0, p.len()) };
+      Ok(p)
+    }
+
+    /// # Safety
+    ///
+    /// The pointer must have been returned by `allocate` with the same layout.
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout);
+  }
+
+  unsafe impl<A: Allocator + ?Sized> Allocator for &A {
+    #[inline(always)]
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+      (**self).allocate(layout)
+    }
+
+    #[inline(always)]
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+      (**self).allocate_zeroed(layout)
+    }
+
+    #[inline(always)]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+      unsafe { (**self).deallocate(ptr, layout) }
+    }
+  }
+
+  /// The global memory allocator.
+
+  #[derive(Clone, Copy, Debug, Default)]
+  pub struct Global;
+
+  impl Global {
+    #[inline(always)]
+    fn alloc_impl(layout: Layout, zeroed: bool) -> Result<NonNull<[u8]>, AllocError> {
+      let p =
+        if layout.size() == 0 {
+          ptr::without_provenance_mut(layout.align())
+        } else if zeroed {
+          unsafe { alloc::alloc::alloc_zeroed(layout) }
+        } else {
+          unsafe { alloc::alloc::alloc(layout) }
+        };
+
+      match NonNull::new(p) {
+        None => Err(AllocError),
+        Some(p) => Ok(NonNull::slice_from_raw_parts(p, layout.size())),
+      }
+    }
+  }
+
+  unsafe impl Allocator for Global {
+    #[inline(always)]
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+      Self::alloc_impl(layout, false)
+    }
+
+    #[inline(always)]
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+      Self::alloc_impl(layout, true)
+    }
+
+    #[inline(always)]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+      if layout.size() != 0 {
+        unsafe { alloc::alloc::dealloc(ptr.as_ptr(), layout) };
+      }
+    }
+  }
+}
@@ -0,0 +1,152 @@
+//! This module implements an interner assigning `NonZeroU64` ids to values.
+
+use crate::prelude::*;
+use crate::map::HashMapNZ64;
+use core::hash::Hash;
+use core::hash::Hasher;
+
+/// An interner that assigns each distinct value a `NonZeroU64` id, in the
+/// order `1, 2, 3, ...` of first appearance.
+///
+/// The values are stored once, in order of their ids. The reverse direction is
+/// a map from a fingerprint of each value to its id, with fingerprint
+/// collisions resolved by probing successive fingerprints.
+
+pub struct InternerNZ64<A> {
+  seed: u64,
+  values: Vec<A>,
+  index: HashMapNZ64<NonZeroU64>,
+}
+
+struct Fingerprint(u64);
+
+const K: u64 = 0x9e37_79b9_7f4a_7c15;
+
+impl Hasher for Fingerprint {
+  #[inline(always)]
+  fn write(&mut self, bytes: &[u8]) {
+    for chunk in bytes.chunks(8) {
+      let mut x = [0; 8];
+      x[.. chunk.len()].copy_from_slice(chunk);
+      self.write_u64(u64::from_le_bytes(x));
+    }
+  }
+
+  #[inline(always)]
+  fn write_u64(&mut self, x: u64) {
+    self.0 = (self.0 ^ x).wrapping_mul(K).swap_bytes();
+  }
+
+  #[inline(always)]
+  fn finish(&self) -> u64 {
+    self.0.wrapping_mul(K)
+  }
+}
+
+#[inline(always)]
+fn next(x: NonZeroU64) -> NonZeroU64 {
+  match x.checked_add(1) {
+    None => NonZeroU64::MIN,
+    Some(y) => y,
+  }
+}
+
+impl<A: Hash + Eq> InternerNZ64<A> {
+  /// Creates an empty interner, seeding its hash functions from a
+  /// thread-local random number generator.
+
+  pub fn new() -> Self {
+    rng::with_default(|rng| Self::new_seeded(rng))
+  }
+
+  /// Creates an empty interner, seeding its hash functions from the given
+  /// random number generator.
+
+  pub fn new_seeded(rng: &mut Rng) -> Self {
+    let seed = rng.u64();
+    let index = HashMapNZ64::new_seeded(rng);
+    Self { seed, values: Vec::new(), index }
+  }
+
+  /// Returns the number of distinct values.
+
+  #[inline(always)]
+  pub fn len(&self) -> usize {
+    self.values.len()
+  }
+
+  /// Returns whether the interner contains zero values.
+
+  #[inline(always)]
+  pub fn is_empty(&self) -> bool {
+    self.values.is_empty()
+  }
+
+  #[inline(always)]
+  fn fingerprint(&self, value: &A) -> NonZeroU64 {
+    let mut h = Fingerprint(self.seed);
+    value.hash(&mut h);
+    let x = h.finish();
+    let x = x ^ (x == 0) as u64;
+    unsafe { NonZeroU64::new_unchecked(x) }
+  }
+
+  /// Returns the id of the given value, assigning it the next id if it hasn't
+  /// been seen before.
+
+  pub fn intern(&mut self, value: A) -> NonZeroU64 {
+    let mut f = self.fingerprint(&value);
+
+    loop {
+      match self.index.get(f) {
+        None => break,
+        Some(&id) => {
+          if self.values[id.get() as usize - 1] == value { return id; }
+          f = next(f);
+        }
+      }
+    }
+
+    self.values.push(value);
+    let id = unsafe { NonZeroU64::new_unchecked(self.values.len() as u64) };
+    let _: _ = self.index.insert(f, id);
+    id
+  }
+
+  /// Returns the id of the given value, if it has been seen before.
+
+  pub fn lookup(&self, value: &A) -> Option<NonZeroU64> {
+    let mut f = self.fingerprint(value);
+
+    loop {
+      let &id = self.index.get(f)?;
+      if self.values[id.get() as usize - 1] == *value { return Some(id); }
+      f = next(f);
+    }
+  }
+
+  /// Returns the value with the given id, if one has been assigned.
+
+  #[inline(always)]
+  pub fn resolve(&self, id: NonZeroU64) -> Option<&A> {
+    self.values.get(id.get() as usize - 1)
+  }
+}
+
+impl<A: Hash + Eq> Default for InternerNZ64<A> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<A: fmt::Debug> fmt::Debug for InternerNZ64<A> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+    let mut f = f.debug_map();
+
+    for (i, value) in self.values.iter().enumerate() {
+      let _: _ = f.entry(&(i + 1), value);
+    }
+
+    f.finish()
+  }
+}
@@ -1,12 +1,35 @@
 //! This crate implements a fast hash map and hash set keyed by `NonZeroU64`s.
+//!
+//! The crate is `no_std`, but requires `alloc`. The default `std` feature
+//! adds a thread-local random number generator for seeding new maps. Without
+//! it, each new map is seeded directly from the operating system.
+//!
+//! The nightly-only `allocator_api` feature lets a map allocate its table
+//! from any [`core::alloc::Allocator`].
+
+#![no_std]
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+
+#[cfg(feature = "std")]
+extern crate std;
 
 mod prelude;
+pub mod allocator;
+pub mod intern;
 pub mod map;
+pub mod mixer;
 pub mod rng;
 pub mod set;
+pub mod soa;
 pub mod two;
 pub mod ptr;
 
+#[cfg(feature = "serde")]
+mod serde_impl;
+
+pub use map::checked_key;
+pub use map::is_valid_key;
+
 pub fn u64_get(t: &map::HashMapNZ64<u64>, key: core::num::NonZeroU64) -> Option<&u64> {
   t.get(key)
 }
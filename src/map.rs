@@ -4,20 +4,36 @@ use crate::prelude::*;
 
 /// A fast hash map keyed by `NonZeroU64`s.
 
-pub struct HashMapNZ64<T> {
-  seeds: Seeds,
+pub struct HashMapNZ64<T, A: Allocator = Global> {
+  mixer: Mixer,
   table: *const Slot<T>, // covariant in `T`
   shift: usize,
   space: isize,
   check: *const Slot<T>,
+  epoch: u64,
+  grows: u64,
+  autoshrink: bool,
+  alloc: A,
 }
 
-unsafe impl<T: Send> Send for HashMapNZ64<T> {}
-
-unsafe impl<T: Sync> Sync for HashMapNZ64<T> {}
-
-#[derive(Clone, Copy)]
-struct Seeds(u64, u64);
+unsafe impl<T: Send, A: Allocator + Send> Send for HashMapNZ64<T, A> {}
+
+unsafe impl<T: Sync, A: Allocator + Sync> Sync for HashMapNZ64<T, A> {}
+
+// NB: The items in the table are sorted by decreasing hash, and each item sits
+// in the first free slot at or after its ideal slot. Both `insert` and `remove`
+// preserve this, so the layout of the table is determined by just the set of
+// stored hashes. In particular, a table that has seen lots of churn is laid
+// out exactly as if its items had been freshly inserted, so there is never any
+// need to compact it.
+//
+// For the same reason, no single item can be moved closer to its ideal slot.
+// Each slot between an item's ideal slot and its actual slot holds an item
+// with a greater hash, and moving the item past it would break the order that
+// lookups rely on to stop early.
+//
+// The tests `test_layout_after_churn` and `test_canonical_layout` check both
+// of these claims.
 
 #[repr(C)]
 struct Slot<T> {
@@ -27,41 +43,139 @@ struct Slot<T> {
 
 static ZERO: u64 = 0;
 
+const POISON: u8 = 0xa5;
+
 const INITIAL_S: usize = 60;                        // shift
 const INITIAL_C: isize = 1 << (64 - INITIAL_S - 1); // capacity
-const INITIAL_D: usize = 1 << (64 - INITIAL_S);     // ?
 const INITIAL_E: usize = 8;                         // extra slots
-const INITIAL_N: usize = INITIAL_D + INITIAL_E;     // table length, total
 const INITIAL_R: isize = INITIAL_C;                 // remaining capacity
 
 #[inline(always)]
-fn invert(a: u64) -> u64 {
-  // https://arxiv.org/abs/2204.04342
+fn spot(shift: usize, h: u64) -> isize {
+  // NB: The shift is always at least one, so the result is non-negative and
+  // less than the number of regular slots, keeping `t.offset(- spot(s, h))`
+  // within the table.
+
+  debug_assert!((1 ..= 63).contains(&shift));
+
+  h.wrapping_shr(shift as u32) as isize
+}
+
+/// The error returned when reserving space in a map fails.
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TryReserveError {
+  /// The required table size exceeds the maximum for this platform.
+  CapacityOverflow,
+  /// The allocator failed to allocate a table with the given layout.
+  AllocError { layout: Layout },
+}
+
+impl fmt::Display for TryReserveError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+    match self {
+      Self::CapacityOverflow => f.write_str("capacity overflow"),
+      Self::AllocError { .. } => f.write_str("memory allocation failed"),
+    }
+  }
+}
+
+/// The error returned when inserting into a map fails, carrying back the value
+/// that couldn't be inserted.
+
+#[derive(Debug)]
+pub struct TryInsertError<T> {
+  pub error: TryReserveError,
+  pub value: T,
+}
+
+impl<T> fmt::Display for TryInsertError<T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+    fmt::Display::fmt(&self.error, f)
+  }
+}
+
+#[inline(always)]
+fn table_layout<T>(u: usize, v: usize) -> Result<Layout, TryReserveError> {
+  // Returns the layout of a table with `2 ** u` regular slots and `2 ** v`
+  // extra slots.
+
+  if ! (1 ..= 63).contains(&u) { return Err(TryReserveError::CapacityOverflow); }
+  if u > usize::BITS as usize - 1 { return Err(TryReserveError::CapacityOverflow); }
+  if v > usize::BITS as usize - 2 { return Err(TryReserveError::CapacityOverflow); }
+
+  let n = (1 << u) + (1 << v);
+
+  if n > isize::MAX as usize / mem::size_of::<Slot<T>>() { return Err(TryReserveError::CapacityOverflow); }
 
-  let x = a.wrapping_mul(3) ^ 2;
-  let y = 1u64.wrapping_sub(a.wrapping_mul(x));
-  let x = x.wrapping_mul(y.wrapping_add(1));
-  let y = y.wrapping_mul(y);
-  let x = x.wrapping_mul(y.wrapping_add(1));
-  let y = y.wrapping_mul(y);
-  let x = x.wrapping_mul(y.wrapping_add(1));
-  let y = y.wrapping_mul(y);
-  let x = x.wrapping_mul(y.wrapping_add(1));
-  x
+  let align = mem::align_of::<Slot<T>>();
+  let size = n * mem::size_of::<Slot<T>>();
+  Ok(unsafe { Layout::from_size_align_unchecked(size, align) })
 }
 
 #[inline(always)]
-fn spot(shift: usize, h: u64) -> isize {
-  h.wrapping_shr(shift as u32) as isize
+fn handle_reserve(r: Result<(), TryReserveError>) {
+  match r {
+    Ok(()) => {}
+    Err(TryReserveError::CapacityOverflow) => panic!("capacity overflow"),
+    Err(TryReserveError::AllocError { layout }) => match alloc::alloc::handle_alloc_error(layout) {},
+  }
+}
+
+#[inline(always)]
+fn prefetch<T>(p: *const T) {
+  // Hints that the cache line holding `p` will soon be read. This is only a
+  // hint, so `p` needn't be valid, and elsewhere it's a no-op.
+
+  #[cfg(all(target_arch = "x86_64", target_feature = "sse"))]
+  unsafe { core::arch::x86_64::_mm_prefetch(p as *const i8, core::arch::x86_64::_MM_HINT_T0) };
+
+  #[cfg(not(all(target_arch = "x86_64", target_feature = "sse")))]
+  let _: _ = p;
+}
+
+unsafe fn merge_slots<T, F>(a: *const Slot<T>, b: *const Slot<T>, c: *const Slot<T>, d: *const Slot<T>, mut f: F)
+where
+  F: FnMut(u64, Option<*const Slot<T>>, Option<*const Slot<T>>)
+{
+  // Visits the union of the hashes stored in the slots from `a` up to `b` and
+  // from `c` up to `d` in decreasing order, which is the order of increasing
+  // address within each range. Calls `f` with each hash and the slot holding
+  // it in each range, if any.
+
+  let mut p = a;
+  let mut r = c;
+
+  loop {
+    while p != b && unsafe { &*p }.hash == 0 { p = unsafe { p.add(1) }; }
+    while r != d && unsafe { &*r }.hash == 0 { r = unsafe { r.add(1) }; }
+
+    let x = if p != b { unsafe { &*p }.hash } else { 0 };
+    let y = if r != d { unsafe { &*r }.hash } else { 0 };
+
+    if x == 0 && y == 0 { break; }
+
+    let h = max(x, y);
+    let i = if x == h { p = unsafe { p.add(1) }; Some(unsafe { p.sub(1) }) } else { None };
+    let j = if y == h { r = unsafe { r.add(1) }; Some(unsafe { r.sub(1) }) } else { None };
+
+    f(h, i, j);
+  }
+}
+
+/// Returns whether the given integer is a valid key, that is, whether it is
+/// nonzero.
+
+#[inline(always)]
+pub const fn is_valid_key(x: u64) -> bool {
+  x != 0
 }
 
+/// Converts the given integer into a key, if it is valid.
+
 #[inline(always)]
-fn hash(Seeds(a, b): Seeds, x: NonZeroU64) -> NonZeroU64 {
-  let x = x.get();
-  let x = x.wrapping_mul(a);
-  let x = x.swap_bytes();
-  let x = x.wrapping_mul(b);
-  unsafe { NonZeroU64::new_unchecked(x) }
+pub const fn checked_key(x: u64) -> Option<NonZeroU64> {
+  NonZeroU64::new(x)
 }
 
 impl<T> HashMapNZ64<T> {
@@ -70,7 +184,7 @@ impl<T> HashMapNZ64<T> {
 
   #[inline(always)]
   pub fn new() -> Self {
-    rng::thread_local::with(|rng| Self::new_seeded(rng))
+    rng::with_default(|rng| Self::new_seeded(rng))
   }
 
   /// Creates an empty map, seeding the hash function from the given random
@@ -78,18 +192,188 @@ impl<T> HashMapNZ64<T> {
 
   #[inline(always)]
   pub fn new_seeded(rng: &mut Rng) -> Self {
-    let a = rng.u64() | 1;
-    let b = invert(a);
+    Self::new_with_mixer(Mixer::new_seeded(rng))
+  }
+
+  /// Creates an empty map that hashes keys with the given mixer.
+  ///
+  /// Maps created with the same mixer hash keys identically, which makes
+  /// their layouts reproducible. But the random mixer chosen by
+  /// [`new`](Self::new) is what protects against inputs crafted to collide,
+  /// so a fixed mixer shouldn't be used with untrusted keys.
+
+  #[inline(always)]
+  pub const fn new_with_mixer(mixer: Mixer) -> Self {
+    Self::new_with_mixer_in(mixer, Global)
+  }
+
+  /// Creates an empty map with room for at least `capacity` items, in the
+  /// sense of [`reserve`](Self::reserve).
+  ///
+  /// # Panics
+  ///
+  /// Panics when allocation fails.
+
+  pub fn with_capacity(capacity: usize) -> Self {
+    let mut t = Self::new();
+    t.reserve(capacity);
+    t
+  }
+
+  /// Creates an empty map like [`new`](Self::new), except that
+  /// [`remove`](Self::remove) shrinks the table once the load falls below one
+  /// eighth. This keeps a map that grows large and then loses most of its
+  /// items from holding on to its memory, at the cost of moving the table.
+  ///
+  /// Because such a removal may move the table, it voids any guarantee from
+  /// [`pin_capacity`](Self::pin_capacity).
 
+  pub fn new_with_autoshrink() -> Self {
+    let mut t = Self::new();
+    t.autoshrink = true;
+    t
+  }
+
+  /// Creates a map from parallel slices of keys and values. Later duplicate
+  /// keys replace earlier ones.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the slices have different lengths.
+
+  pub fn from_columns(keys: &[NonZeroU64], values: &[T]) -> Self where T: Clone {
+    assert!(keys.len() == values.len(), "keys and values have different lengths");
+
+    let mut t = Self::new();
+    t.reserve(keys.len());
+
+    for (&key, value) in keys.iter().zip(values.iter()) {
+      let _: _ = t.insert(key, value.clone());
+    }
+
+    t
+  }
+
+  /// Creates a map from parallel vectors of keys and values, taking ownership
+  /// of the values. Later duplicate keys replace earlier ones.
+  ///
+  /// # Panics
+  ///
+  /// Panics if the vectors have different lengths.
+
+  pub fn from_columns_owned(keys: Vec<NonZeroU64>, values: Vec<T>) -> Self {
+    assert!(keys.len() == values.len(), "keys and values have different lengths");
+
+    let mut t = Self::new();
+    t.reserve(keys.len());
+
+    for (key, value) in keys.into_iter().zip(values) {
+      let _: _ = t.insert(key, value);
+    }
+
+    t
+  }
+
+  /// Creates a map from the given items. When a key appears more than once,
+  /// `keep(existing, candidate)` decides whether the candidate value replaces
+  /// the existing one.
+
+  pub fn from_iter_keep<I, F>(iter: I, keep: F) -> Self
+  where
+    I: IntoIterator<Item = (NonZeroU64, T)>,
+    F: Fn(&T, &T) -> bool
+  {
+    let mut t = Self::new();
+
+    for (key, value) in iter {
+      match t.entry(key) {
+        Entry::Occupied(mut o) => {
+          if keep(o.get(), &value) { let _: _ = o.insert(value); }
+        }
+        Entry::Vacant(v) => {
+          let _: _ = v.insert(value);
+        }
+      }
+    }
+
+    t
+  }
+
+  /// Creates an empty map that hashes keys with the mixer created from the
+  /// given seed by [`Mixer::new`]. Unlike [`new`](Self::new), this can be
+  /// used to initialize a `static`.
+
+  #[inline(always)]
+  pub const fn new_const_seeded(seed: [u64; 2]) -> Self {
+    Self::new_with_mixer(Mixer::new(seed))
+  }
+}
+
+impl<T, A: Allocator> HashMapNZ64<T, A> {
+  /// Creates an empty map that allocates its table with the given allocator,
+  /// seeding the hash function from a thread-local random number generator.
+
+  #[inline(always)]
+  pub fn new_in(alloc: A) -> Self {
+    rng::with_default(|rng| Self::new_with_mixer_in(Mixer::new_seeded(rng), alloc))
+  }
+
+  /// Creates an empty map that hashes keys with the given mixer and allocates
+  /// its table with the given allocator.
+
+  #[inline(always)]
+  pub const fn new_with_mixer_in(mixer: Mixer, alloc: A) -> Self {
     Self {
-      seeds: Seeds(a, b),
+      mixer,
       table: ptr::null(),
       shift: INITIAL_S,
       space: INITIAL_R,
       check: ptr::null(),
+      epoch: 0,
+      grows: 0,
+      autoshrink: false,
+      alloc,
     }
   }
 
+  /// Returns the allocator used for the table.
+
+  #[inline(always)]
+  pub fn allocator(&self) -> &A {
+    &self.alloc
+  }
+
+  /// Returns the mixer used to hash keys.
+
+  #[inline(always)]
+  pub fn mixer(&self) -> Mixer {
+    self.mixer
+  }
+
+  /// Returns the seed of the mixer used to hash keys. Passing it to
+  /// [`Mixer::new`] and then to [`new_with_mixer`](Self::new_with_mixer)
+  /// creates a map that lays out its items identically, unless the mixer was
+  /// created by [`Mixer::new_no_swap`].
+
+  #[inline(always)]
+  pub fn seed(&self) -> [u64; 2] {
+    self.mixer.seed()
+  }
+
+  /// Returns a counter that is incremented by every structural mutation of
+  /// the map, that is, by every insertion of a new key, removal, growth of the
+  /// table, and nontrivial `clear`, `reset` or `drain`. Each of the last three
+  /// counts as a single mutation, however many items it removes. Replacing or
+  /// modifying a value leaves it unchanged.
+  ///
+  /// Comparing the epoch before and after a batch of reads detects whether a
+  /// writer intervened.
+
+  #[inline(always)]
+  pub fn epoch(&self) -> u64 {
+    self.epoch
+  }
+
   /// Returns the number of items.
 
   #[inline(always)]
@@ -108,6 +392,233 @@ impl<T> HashMapNZ64<T> {
     self.len() == 0
   }
 
+  /// Returns the number of items the map can hold before inserting another
+  /// new key grows the table, or zero if no table is allocated.
+  ///
+  /// This is the capacity by load alone. An insertion that pushes an item into
+  /// the final slot of the table also grows it, so keys that cluster at the
+  /// end of the table may grow it sooner; see
+  /// [`pin_capacity`](Self::pin_capacity).
+
+  #[inline(always)]
+  pub fn capacity(&self) -> usize {
+    if self.table.is_null() { return 0; }
+
+    self.len() + max(self.space, 0) as usize
+  }
+
+  /// Returns whether the next `additional` insertions are guaranteed not to
+  /// grow the table, and hence not to move it. Doesn't allocate or otherwise
+  /// modify the map.
+  ///
+  /// Removals never grow the table, so they may be freely interleaved with
+  /// the insertions. The exception is a map created with
+  /// [`new_with_autoshrink`](Self::new_with_autoshrink), where a removal may
+  /// shrink the table, and hence move it.
+
+  pub fn pin_capacity(&self, additional: usize) -> bool {
+    let t = self.table;
+
+    if t.is_null() { return additional == 0; }
+
+    let r = self.space;
+    let b = self.check;
+
+    if r < 0 || additional > r as usize { return false; }
+
+    // Besides running out of space, the table grows when an insertion pushes
+    // an item into the final slot. Each insertion can extend the last
+    // occupied slot at or after `t` by at most one, so we find that slot and
+    // check whether the remaining distance to `b` suffices.
+
+    let mut p = unsafe { b.sub(1) };
+
+    while p >= t && unsafe { &*p }.hash == 0 {
+      p = unsafe { p.sub(1) };
+    }
+
+    additional < unsafe { b.offset_from(p) } as usize
+  }
+
+  /// Grows the table, if necessary, so that the next `additional` insertions
+  /// of new keys are guaranteed not to grow it again, in the sense of
+  /// [`pin_capacity`](Self::pin_capacity).
+  ///
+  /// Unlike reserving space for `additional` more items, this also accounts
+  /// for insertions that cluster at the end of the table, so it may allocate
+  /// up to `additional` extra slots beyond those needed for the load alone.
+  /// Removals never grow the table, but they also don't offset insertions, so
+  /// `additional` should count every insertion of a new key in the batch.
+  ///
+  /// On a map created with [`new_with_autoshrink`](Self::new_with_autoshrink),
+  /// a removal may still shrink the table, and hence move it.
+  ///
+  /// # Panics
+  ///
+  /// Panics when allocation fails.
+
+  pub fn reserve_for_transaction(&mut self, additional: usize) {
+    if additional == 0 { return; }
+
+    if self.table.is_null() {
+      unsafe { self.internal_init_table(64 - INITIAL_S, INITIAL_E.trailing_zeros() as usize) };
+    }
+
+    while ! self.pin_capacity(additional) {
+      let t = self.table;
+      let s = self.shift;
+      let r = self.space;
+      let b = self.check;
+
+      let e = unsafe { b.offset_from(t) } as usize;
+      let u = 64 - s;
+      let v = e.trailing_zeros() as usize;
+
+      if r < 0 || additional > r as usize {
+        unsafe { self.internal_resize_table(u + 1, v) };
+      } else {
+        unsafe { self.internal_resize_table(u, v + 1) };
+      }
+    }
+  }
+
+  /// Grows the table, if necessary, so that it has room for at least
+  /// `target` items in total, allocating it if the map has none yet.
+  ///
+  /// Inserting keys that happen to cluster at the end of the table may still
+  /// grow it; see [`reserve_for_transaction`](Self::reserve_for_transaction)
+  /// for a stronger guarantee.
+  ///
+  /// # Panics
+  ///
+  /// Panics when allocation fails.
+
+  pub fn grow_to(&mut self, target: usize) {
+    self.reserve(target.saturating_sub(self.len()));
+  }
+
+  /// Tries to grow the table so that it has room for at least `additional`
+  /// more items. On failure, the map is left unchanged.
+  ///
+  /// Inserting keys that happen to cluster at the end of the table may still
+  /// grow it; see [`reserve_for_transaction`](Self::reserve_for_transaction)
+  /// for a stronger guarantee.
+
+  pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+    if additional == 0 { return Ok(()); }
+
+    let t = self.table;
+    let k = self.len();
+    let n = k.checked_add(additional).ok_or(TryReserveError::CapacityOverflow)?;
+    let w = (usize::BITS - (n - 1).leading_zeros()) as usize + 1;
+
+    if t.is_null() {
+      let u = max(w, 64 - INITIAL_S);
+      return unsafe { self.internal_try_init_table(u, INITIAL_E.trailing_zeros() as usize) };
+    }
+
+    let s = self.shift;
+    let r = self.space;
+
+    if r >= 0 && additional <= r as usize { return Ok(()); }
+
+    let b = self.check;
+    let e = unsafe { b.offset_from(t) } as usize;
+    let u = max(w, 64 - s);
+    let v = e.trailing_zeros() as usize;
+
+    unsafe { self.internal_try_resize_table(u, v) }
+  }
+
+  /// Grows the table, if necessary, so that it has room for at least
+  /// `additional` more items, allocating it if the map has none yet.
+  ///
+  /// Inserting keys that happen to cluster at the end of the table may still
+  /// grow it; see [`reserve_for_transaction`](Self::reserve_for_transaction)
+  /// for a stronger guarantee.
+  ///
+  /// # Panics
+  ///
+  /// Panics when allocation fails.
+
+  pub fn reserve(&mut self, additional: usize) {
+    handle_reserve(self.try_reserve(additional));
+  }
+
+  /// Returns the number of bytes that [`shrink_to_fit`](Self::shrink_to_fit)
+  /// would currently release, without shrinking the table.
+
+  pub fn shrinkable_bytes(&self) -> usize {
+    match self.internal_shrunk_shape() {
+      None => 0,
+      Some((u, v)) => {
+        let n = (1 << u) + (1 << v);
+        (self.internal_num_slots() - n) * mem::size_of::<Slot<T>>()
+      }
+    }
+  }
+
+  /// Shrinks the table to the smallest one that holds the current items
+  /// without exceeding the maximum load. Does nothing if the map is empty or
+  /// the table is already that small.
+  ///
+  /// # Panics
+  ///
+  /// Panics when allocation fails.
+
+  pub fn shrink_to_fit(&mut self) {
+    if let Some((u, v)) = self.internal_shrunk_shape() {
+      unsafe { self.internal_resize_table(u, v) };
+    }
+  }
+
+  fn internal_shrunk_shape(&self) -> Option<(usize, usize)> {
+    // Returns the base two logarithms of the numbers of regular and extra
+    // slots in the smallest table that holds the current items, if that table
+    // is smaller than the current one.
+    //
+    // The regular slots are determined by the load. For the extra slots, we
+    // find where the last item would land in the new table.
+
+    let t = self.table;
+    let k = self.len();
+
+    if t.is_null() || k == 0 { return None; }
+
+    let s = self.shift;
+    let b = self.check;
+    let d = 1 << (64 - s);
+    let a = unsafe { t.sub(d - 1) };
+
+    let new_u = max(64 - INITIAL_S, (usize::BITS - (k - 1).leading_zeros()) as usize + 1);
+    let new_s = 64 - new_u;
+    let new_d = 1 << new_u;
+
+    let mut p = a;
+    let mut i = 0;
+
+    while p < b {
+      let x = unsafe { &*p }.hash;
+
+      if x != 0 {
+        i = max(i, new_d - 1 - spot(new_s, x) as usize) + 1;
+      }
+
+      p = unsafe { p.add(1) };
+    }
+
+    // The slot after the last item, at index `i`, must be at or before the
+    // final slot, at index `new_d + new_e - 1`.
+
+    let m = (i + 1).saturating_sub(new_d);
+    let new_v = max(INITIAL_E.trailing_zeros() as usize, (usize::BITS - m.saturating_sub(1).leading_zeros()) as usize);
+    let new_n = new_d + (1 << new_v);
+
+    if new_n >= self.internal_num_slots() { return None; }
+
+    Some((new_u, new_v))
+  }
+
   /// Returns whether the map contains the given key.
 
   #[inline(always)]
@@ -116,9 +627,9 @@ impl<T> HashMapNZ64<T> {
 
     if t.is_null() { return false; }
 
-    let m = self.seeds;
+    let m = self.mixer;
     let s = self.shift;
-    let h = hash(m, key).get();
+    let h = m.hash(key).get();
 
     let mut p = unsafe { t.offset(- spot(s, h)) };
     let mut x = unsafe { &*p }.hash;
@@ -131,6 +642,14 @@ impl<T> HashMapNZ64<T> {
     x == h
   }
 
+  /// Returns the given key and a reference to its associated value, if
+  /// present.
+
+  #[inline(always)]
+  pub fn get_key_value(&self, key: NonZeroU64) -> Option<(NonZeroU64, &T)> {
+    Some((key, self.get(key)?))
+  }
+
   /// Returns a reference to the value associated with the given key, if
   /// present.
 
@@ -140,9 +659,9 @@ impl<T> HashMapNZ64<T> {
 
     if t.is_null() { return None; }
 
-    let m = self.seeds;
+    let m = self.mixer;
     let s = self.shift;
-    let h = hash(m, key).get();
+    let h = m.hash(key).get();
 
     let mut p = unsafe { t.offset(- spot(s, h)) };
     let mut x = unsafe { &*p }.hash;
@@ -157,6 +676,28 @@ impl<T> HashMapNZ64<T> {
     Some(unsafe { (&*p).data.assume_init_ref() })
   }
 
+  /// Returns a reference to the value associated with the given key, if
+  /// present.
+  ///
+  /// This is the same as [`get`](Self::get). The map doesn't collect any
+  /// statistics about lookups, so the two are interchangeable.
+
+  #[inline(always)]
+  pub fn peek(&self, key: NonZeroU64) -> Option<&T> {
+    self.get(key)
+  }
+
+  /// Returns a reference to the value associated with the given key if
+  /// present, and otherwise returns `default`.
+
+  #[inline(always)]
+  pub fn get_or_cow<'a>(&'a self, key: NonZeroU64, default: &'a T) -> &'a T {
+    match self.get(key) {
+      None => default,
+      Some(v) => v,
+    }
+  }
+
   /// Returns a mutable reference to the value associated with the given key,
   /// if present.
 
@@ -166,9 +707,9 @@ impl<T> HashMapNZ64<T> {
 
     if t.is_null() { return None; }
 
-    let m = self.seeds;
+    let m = self.mixer;
     let s = self.shift;
-    let h = hash(m, key).get();
+    let h = m.hash(key).get();
 
     let mut p = unsafe { t.offset(- spot(s, h)) };
     let mut x = unsafe { &*p }.hash;
@@ -183,157 +724,831 @@ impl<T> HashMapNZ64<T> {
     Some(unsafe { (&mut *p).data.assume_init_mut() })
   }
 
-  #[inline(never)]
-  #[cold]
-  unsafe fn internal_init_table_and_insert(&mut self, key: NonZeroU64, value: T) {
-    assert!(INITIAL_N <= isize::MAX as usize / mem::size_of::<Slot<T>>());
+  /// Returns references to the values associated with the given keys, each of
+  /// which is `None` if its key is absent.
+  ///
+  /// This computes every key's ideal slot and prefetches it before probing
+  /// for any of them, so that the cache misses of the lookups overlap rather
+  /// than happening one after another. That pays off when the table is too
+  /// large to stay in cache.
 
-    let align = mem::align_of::<Slot<T>>();
-    let size = INITIAL_N * mem::size_of::<Slot<T>>();
-    let layout = unsafe { Layout::from_size_align_unchecked(size, align) };
+  pub fn get_many<const N: usize>(&self, keys: [NonZeroU64; N]) -> [Option<&T>; N] {
+    let t = self.table;
 
-    let a = unsafe { alloc::alloc::alloc_zeroed(layout) } as *mut Slot<T>;
-    if a.is_null() { match alloc::alloc::handle_alloc_error(layout) {} }
+    if t.is_null() { return [None; N]; }
 
-    let t = unsafe { a.add(INITIAL_D - 1) };
-    let b = unsafe { a.add(INITIAL_N - 1) };
+    let m = self.mixer;
+    let s = self.shift;
+    let h = keys.map(|key| m.hash(key).get());
+    let p = h.map(|h| unsafe { t.offset(- spot(s, h)) });
 
-    let m = self.seeds;
-    let h = hash(m, key).get();
-    let p = unsafe { t.offset(- spot(INITIAL_S, h)) };
+    for &p in p.iter() { prefetch(p) }
 
-    unsafe { &mut *p }.hash = h;
-    unsafe { &mut *p }.data = MaybeUninit::new(value);
+    let mut a = [None; N];
+
+    for i in 0 .. N {
+      let h = h[i];
+      let mut p = p[i];
+      let mut x = unsafe { &*p }.hash;
+
+      while x > h {
+        p = unsafe { p.add(1) };
+        x = unsafe { &*p }.hash;
+      }
+
+      if x == h { a[i] = Some(unsafe { (&*p).data.assume_init_ref() }); }
+    }
+
+    a
+  }
+
+  /// Returns mutable references to the values associated with the given keys.
+  /// Returns `None` if any key is absent or if two of the keys are equal.
+
+  pub fn get_many_mut<const N: usize>(&mut self, keys: [NonZeroU64; N]) -> Option<[&mut T; N]> {
+    for i in 1 .. N {
+      for j in 0 .. i {
+        if keys[i] == keys[j] { return None; }
+      }
+    }
+
+    unsafe { self.get_many_unchecked_mut(keys) }
+  }
+
+  /// Returns mutable references to the values associated with the given keys.
+  /// Returns `None` if any key is absent.
+  ///
+  /// # Safety
+  ///
+  /// The keys must be pairwise distinct.
+
+  pub unsafe fn get_many_unchecked_mut<const N: usize>(&mut self, keys: [NonZeroU64; N]) -> Option<[&mut T; N]> {
+    let mut p = [ptr::null_mut(); N];
+
+    for i in 0 .. N {
+      p[i] = self.internal_find(keys[i]);
+      if p[i].is_null() { return None; }
+    }
+
+    Some(p.map(|p| unsafe { (&mut *p).data.assume_init_mut() }))
+  }
+
+  #[inline(always)]
+  fn internal_find(&self, key: NonZeroU64) -> *mut Slot<T> {
+    let t = self.table as *mut Slot<T>;
+
+    if t.is_null() { return ptr::null_mut(); }
+
+    let m = self.mixer;
+    let s = self.shift;
+    let h = m.hash(key).get();
+
+    let mut p = unsafe { t.offset(- spot(s, h)) };
+    let mut x = unsafe { &*p }.hash;
+
+    while x > h {
+      p = unsafe { p.add(1) };
+      x = unsafe { &*p }.hash;
+    }
+
+    if x != h { return ptr::null_mut(); }
+
+    p
+  }
+
+  /// Returns the index, counting from the start of the allocation, of the
+  /// slot holding the given key, if present.
+  ///
+  /// Inserting or removing any item may move other items, so the index is
+  /// only valid until the next mutation of the map.
+
+  pub fn slot_index_of(&self, key: NonZeroU64) -> Option<usize> {
+    let t = self.table;
+
+    if t.is_null() { return None; }
+
+    let m = self.mixer;
+    let s = self.shift;
+    let h = m.hash(key).get();
+
+    let mut p = unsafe { t.offset(- spot(s, h)) };
+    let mut x = unsafe { &*p }.hash;
+
+    while x > h {
+      p = unsafe { p.add(1) };
+      x = unsafe { &*p }.hash;
+    }
+
+    if x != h { return None; }
+
+    let d = 1 << (64 - s);
+    let a = unsafe { t.sub(d - 1) };
+
+    Some(unsafe { p.offset_from(a) } as usize)
+  }
+
+  #[inline(never)]
+  #[cold]
+  unsafe fn internal_init_table_and_insert(&mut self, h: u64, value: T) {
+    unsafe { self.internal_init_table(64 - INITIAL_S, INITIAL_E.trailing_zeros() as usize) };
+
+    let t = self.table as *mut Slot<T>;
+    let p = unsafe { t.offset(- spot(INITIAL_S, h)) };
+
+    unsafe { &mut *p }.hash = h;
+    unsafe { &mut *p }.data = MaybeUninit::new(value);
+
+    self.space = INITIAL_R - 1;
+    self.epoch = self.epoch.wrapping_add(1);
+  }
+
+  #[inline(never)]
+  #[cold]
+  unsafe fn internal_init_table(&mut self, u: usize, v: usize) {
+    handle_reserve(unsafe { self.internal_try_init_table(u, v) });
+  }
+
+  unsafe fn internal_try_init_table(&mut self, u: usize, v: usize) -> Result<(), TryReserveError> {
+    let layout = table_layout::<T>(u, v)?;
+
+    let s = 64 - u;
+    let c = 1 << (64 - s - 1);
+    let d = 1 << (64 - s);
+    let e = 1 << v;
+    let n = d + e;
+
+    let a = match self.alloc.allocate_zeroed(layout) {
+      Err(_) => return Err(TryReserveError::AllocError { layout }),
+      Ok(a) => a.as_ptr() as *mut Slot<T>,
+    };
+
+    let t = unsafe { a.add(d - 1) };
+    let b = unsafe { a.add(n - 1) };
 
     // We only modify `self` after we know that allocation has succeeded.
 
     self.table = t;
-    self.shift = INITIAL_S;
-    self.space = INITIAL_R - 1;
+    self.shift = s;
+    self.space = c;
     self.check = b;
+
+    Ok(())
+  }
+
+  #[inline(never)]
+  #[cold]
+  unsafe fn internal_grow_table(&mut self) {
+    let old_t = self.table;
+    let old_s = self.shift;
+    let old_r = self.space;
+    let old_b = self.check;
+
+    let is_overfull = old_r < 0;
+    let is_overflow = unsafe { &*old_b }.hash != 0;
+
+    let old_e = unsafe { old_b.offset_from(old_t) } as usize;
+    let old_u = 64 - old_s;
+    let old_v = old_e.trailing_zeros() as usize;
+
+    let new_u = old_u + is_overfull as usize;
+    let new_v = old_v + is_overflow as usize;
+
+    unsafe { self.internal_resize_table(new_u, new_v) };
+  }
+
+  /// Moves the items into a newly allocated table with `2 ** new_u` regular
+  /// slots and `2 ** new_v` extra slots. The new table must be large enough
+  /// that the items fit without any of them landing in the final slot.
+
+  #[inline(never)]
+  #[cold]
+  unsafe fn internal_resize_table(&mut self, new_u: usize, new_v: usize) {
+    handle_reserve(unsafe { self.internal_try_resize_table(new_u, new_v) });
   }
 
-  #[inline(never)]
-  #[cold]
-  unsafe fn internal_grow_table(&mut self) {
-    let old_t = self.table as *mut Slot<T>;
-    let old_s = self.shift;
-    let old_r = self.space;
-    let old_b = self.check as *mut Slot<T>;
+  unsafe fn internal_try_resize_table(&mut self, new_u: usize, new_v: usize) -> Result<(), TryReserveError> {
+    let old_t = self.table as *mut Slot<T>;
+    let old_s = self.shift;
+    let old_r = self.space;
+    let old_b = self.check as *mut Slot<T>;
+
+    let old_c = 1 << (64 - old_s - 1);
+    let old_d = 1 << (64 - old_s);
+    let old_e = unsafe { old_b.offset_from(old_t) } as usize;
+    let old_a = unsafe { old_t.sub(old_d - 1) };
+    let old_layout = table_layout::<T>(64 - old_s, old_e.trailing_zeros() as usize)?;
+    let new_layout = table_layout::<T>(new_u, new_v)?;
+
+    let new_s = 64 - new_u;
+    let new_c = 1 << (64 - new_s - 1);
+    let new_d = 1 << (64 - new_s);
+    let new_e = 1 << new_v;
+    let new_n = new_d + new_e;
+    let new_r = old_r + (new_c - old_c);
+
+    let old_b_hash = unsafe { &*old_b }.hash;
+    let is_overflow = old_b_hash != 0;
+
+    // WARNING!
+    //
+    // We must be careful to leave the map in a valid state even if attempting
+    // to allocate a new table results in a panic.
+    //
+    // It turns out that the `is_overfull` state with negative space actually
+    // *is* valid, but the `is_overflow` state *is not* valid.
+    //
+    // In the latter case, we temporarily remove the item in the final slot and
+    // restore it after we have succeeded at everything that might panic. If
+    // allocation fails, we return with the item still removed, so the map is
+    // valid but has leaked the item.
+    //
+    // This is an instance of the infamous PPYP design pattern.
+
+    if is_overflow {
+      unsafe { &mut *old_b }.hash = 0;
+      self.space = old_r + 1;
+    }
+
+    let new_a = match self.alloc.allocate_zeroed(new_layout) {
+      Err(_) => return Err(TryReserveError::AllocError { layout: new_layout }),
+      Ok(a) => a.as_ptr() as *mut Slot<T>,
+    };
+
+    // At this point, we know that allocating a new table has succeeded, so we
+    // undo our earlier `if is_overflow { ... }` block.
+
+    if is_overflow {
+      unsafe { &mut *old_b }.hash = old_b_hash;
+      self.space = old_r;
+    }
+
+    let new_t = unsafe { new_a.add(new_d - 1) };
+    let new_b = unsafe { new_a.add(new_n - 1) };
+
+    let mut p = old_a;
+    let mut q = new_a;
+
+    while p <= old_b {
+      let x = unsafe { &*p }.hash;
+
+      if x != 0 {
+        q = max(q, unsafe { new_t.offset(- spot(new_s, x)) });
+        unsafe { &mut *q }.hash = x;
+        unsafe { &mut *q }.data = MaybeUninit::new(unsafe { (&*p).data.assume_init_read() });
+        q = unsafe { q.add(1) };
+      }
+
+      p = unsafe { p.add(1) };
+    }
+
+    debug_assert!(q <= new_b);
+
+    self.table = new_t;
+    self.shift = new_s;
+    self.space = new_r;
+    self.check = new_b;
+    self.epoch = self.epoch.wrapping_add(1);
+    self.grows += (new_layout.size() > old_layout.size()) as u64;
+
+    // The map is now in a valid state, even if `dealloc` panics.
+
+    unsafe { self.alloc.deallocate(NonNull::new_unchecked(old_a as *mut u8), old_layout) };
+
+    Ok(())
+  }
+
+  /// Inserts the given key and value into the map. Returns the previous value
+  /// associated with given key, if one was present.
+  ///
+  /// # Panics
+  ///
+  /// Panics when allocation fails. If that happens, it is possible for the map
+  /// to leak an arbitrary set of items, but the map will remain in a valid
+  /// state.
+
+  #[inline(always)]
+  pub fn insert(&mut self, key: NonZeroU64, value: T) -> Option<T> {
+    let m = self.mixer;
+    let h = m.hash(key).get();
+    self.internal_insert(h, value)
+  }
+
+  /// Inserts the given key and value into the map, like
+  /// [`insert`](Self::insert), but returns an error carrying back the value
+  /// instead of panicking when growing the table fails. On failure, the map is
+  /// left unchanged.
+
+  pub fn try_insert(&mut self, key: NonZeroU64, value: T) -> Result<Option<T>, TryInsertError<T>> {
+    let m = self.mixer;
+    let h = m.hash(key).get();
+
+    loop {
+      let t = self.table as *mut Slot<T>;
+
+      if t.is_null() {
+        let u = 64 - INITIAL_S;
+        let v = INITIAL_E.trailing_zeros() as usize;
+
+        match unsafe { self.internal_try_init_table(u, v) } {
+          Err(error) => return Err(TryInsertError { error, value }),
+          Ok(()) => continue,
+        }
+      }
+
+      let s = self.shift;
+      let r = self.space;
+      let b = self.check as *mut Slot<T>;
+
+      let mut p = unsafe { t.offset(- spot(s, h)) };
+      let mut x = unsafe { &*p }.hash;
+
+      while x > h {
+        p = unsafe { p.add(1) };
+        x = unsafe { &*p }.hash;
+      }
+
+      if x == h {
+        let v = mem::replace(unsafe { (&mut *p).data.assume_init_mut() }, value);
+        return Ok(Some(v));
+      }
+
+      // Inserting a new key would grow the table if it either runs out of
+      // space or shifts an item into the final slot, so in those cases we grow
+      // the table first, fallibly, and then probe again.
+
+      let mut q = p;
+
+      while unsafe { &*q }.hash != 0 {
+        q = unsafe { q.add(1) };
+      }
+
+      let e = unsafe { b.offset_from(t) } as usize;
+      let u = 64 - s;
+      let v = e.trailing_zeros() as usize;
+      let new_u = u + (r < 1) as usize;
+      let new_v = v + (q == b) as usize;
+
+      if new_u == u && new_v == v {
+        let _: _ = unsafe { self.internal_insert_at(p, h, value) };
+        return Ok(None);
+      }
+
+      if let Err(error) = unsafe { self.internal_try_resize_table(new_u, new_v) } {
+        return Err(TryInsertError { error, value });
+      }
+    }
+  }
+
+  #[inline(always)]
+  fn internal_insert(&mut self, h: u64, value: T) -> Option<T> {
+    let t = self.table as *mut Slot<T>;
+
+    if t.is_null() {
+      unsafe { self.internal_init_table_and_insert(h, value) };
+      return None;
+    }
+
+    let s = self.shift;
+
+    let mut p = unsafe { t.offset(- spot(s, h)) };
+    let mut x = unsafe { &*p }.hash;
+
+    while x > h {
+      p = unsafe { p.add(1) };
+      x = unsafe { &*p }.hash;
+    }
+
+    if x == h {
+      let v = mem::replace(unsafe { (&mut *p).data.assume_init_mut() }, value);
+      return Some(v);
+    }
+
+    let _: _ = unsafe { self.internal_insert_at(p, h, value) };
+
+    None
+  }
+
+  /// Inserts a new item with hash `h` at `p`, which must be the slot where a
+  /// probe for `h` stopped without finding it. Returns a pointer to the slot
+  /// now holding the item, which may be in a new table.
+
+  #[inline(always)]
+  unsafe fn internal_insert_at(&mut self, p: *mut Slot<T>, h: u64, value: T) -> *mut Slot<T> {
+    let o = p;
+    let mut p = p;
+    let mut x = unsafe { &*p }.hash;
+    let mut v = value;
+
+    unsafe { &mut *p }.hash = h;
+
+    while x != 0 {
+      v = mem::replace(unsafe { (&mut *p).data.assume_init_mut() }, v);
+      p = unsafe { p.add(1) };
+      x = mem::replace(&mut unsafe { &mut *p }.hash, x);
+    }
+
+    unsafe { &mut *p }.data = MaybeUninit::new(v);
+
+    let r = self.space - 1;
+    self.space = r;
+    self.epoch = self.epoch.wrapping_add(1);
+    let b = self.check as *mut Slot<T>;
+
+    if r < 0 || p == b {
+      unsafe { self.internal_grow_table() };
+      return unsafe { self.internal_probe(h) };
+    }
+
+    o
+  }
+
+  /// Returns a pointer to the slot holding the item with hash `h`, which must
+  /// be present.
+
+  #[inline(always)]
+  unsafe fn internal_probe(&self, h: u64) -> *mut Slot<T> {
+    let t = self.table as *mut Slot<T>;
+    let s = self.shift;
+
+    let mut p = unsafe { t.offset(- spot(s, h)) };
+
+    while unsafe { &*p }.hash != h {
+      p = unsafe { p.add(1) };
+    }
+
+    p
+  }
+
+  /// Removes every item whose value satisfies the predicate, and returns their
+  /// keys in increasing order.
+
+  pub fn remove_where_collect<F>(&mut self, mut pred: F) -> Box<[NonZeroU64]>
+  where
+    F: FnMut(&T) -> bool
+  {
+    let mut a = Vec::new();
+
+    if self.table.is_null() { return a.into_boxed_slice(); }
+
+    let m = self.mixer.invert();
+    let mut k = self.len();
+    let mut p = self.check as *mut Slot<T>;
+
+    // We traverse the table in reverse order. Removing an item only moves the
+    // items after it, all of which have already been visited.
+
+    while k != 0 {
+      p = unsafe { p.sub(1) };
+      let x = unsafe { &*p }.hash;
+
+      if x != 0 {
+        k -= 1;
+
+        if pred(unsafe { (&*p).data.assume_init_ref() }) {
+          let _: _ = unsafe { self.internal_remove_at(p, false) };
+          a.push(m.hash(unsafe { NonZeroU64::new_unchecked(x) }));
+        }
+      }
+    }
+
+    a.sort();
+    a.into_boxed_slice()
+  }
+
+  /// Retains only the items for which `f` returns `true`, calling it with each
+  /// key and a mutable reference to its value, and removes the rest.
+
+  pub fn retain<F>(&mut self, mut f: F)
+  where
+    F: FnMut(NonZeroU64, &mut T) -> bool
+  {
+    if self.table.is_null() { return; }
+
+    let m = self.mixer.invert();
+    let mut k = self.len();
+    let mut p = self.check as *mut Slot<T>;
+
+    // As in `remove_where_collect`, we traverse the table in reverse order, so
+    // removing an item only moves items that have already been visited.
+
+    while k != 0 {
+      p = unsafe { p.sub(1) };
+      let x = unsafe { &*p }.hash;
+
+      if x != 0 {
+        k -= 1;
+
+        let key = m.hash(unsafe { NonZeroU64::new_unchecked(x) });
+
+        if ! f(key, unsafe { (&mut *p).data.assume_init_mut() }) {
+          let _: _ = unsafe { self.internal_remove_at(p, false) };
+        }
+      }
+    }
+  }
+
+  /// Returns a pair of new maps holding clones of the items that do and don't
+  /// satisfy the predicate, respectively, leaving this map unchanged. Both
+  /// maps use the same mixer as this one.
+
+  pub fn partition_ref<F>(&self, pred: F) -> (Self, Self)
+  where
+    T: Clone,
+    A: Clone,
+    F: Fn(NonZeroU64, &T) -> bool
+  {
+    let mut yes = Self::new_with_mixer_in(self.mixer, self.alloc.clone());
+    let mut no = Self::new_with_mixer_in(self.mixer, self.alloc.clone());
+
+    let m = self.mixer.invert();
+    let mut k = self.len();
+    let mut p = self.check;
+
+    while k != 0 {
+      p = unsafe { p.sub(1) };
+      let x = unsafe { &*p }.hash;
+
+      if x != 0 {
+        let key = m.hash(unsafe { NonZeroU64::new_unchecked(x) });
+        let value = unsafe { (&*p).data.assume_init_ref() };
+        let t = if pred(key, value) { &mut yes } else { &mut no };
+        let _: _ = t.internal_insert(x, value.clone());
+        k -= 1;
+      }
+    }
+
+    (yes, no)
+  }
+
+  /// Inserts a clone of every item in `other` into the map. Items from `other`
+  /// replace items with the same key.
+  ///
+  /// This is [`merge_with`](Self::merge_with) with a callback that overwrites
+  /// the map's value, so it takes the same fast path when the mixers match.
+
+  pub fn extend_from_map<B: Allocator>(&mut self, other: &HashMapNZ64<T, B>) where T: Clone {
+    self.merge_with(other, |_, x, y| x.clone_from(y));
+  }
+
+  /// Merges the items of `other` into the map. For each key present in both
+  /// maps, calls `f` with the key, the map's value and the value in `other`.
+  /// Inserts a clone of every other item in `other`.
+  ///
+  /// When both maps use the same mixer and `other` isn't much smaller than
+  /// the map, the two tables are merged into a new table in a single pass
+  /// over their slots, without probing for any item.
+  ///
+  /// # Panics
+  ///
+  /// Panics when allocation fails. If `f` or `clone` panics, the map is left
+  /// in a valid state, but may leak some of its items.
+
+  pub fn merge_with<B: Allocator, F>(&mut self, other: &HashMapNZ64<T, B>, mut f: F)
+  where
+    T: Clone,
+    F: FnMut(NonZeroU64, &mut T, &T)
+  {
+    if other.is_empty() { return; }
+
+    // A merge makes a few sequential passes over both tables, which is much
+    // cheaper per slot than a random probe, but still costs time proportional
+    // to the size of the map. So we only merge when `other` is large enough to
+    // pay for that.
+
+    if self.mixer == other.mixer && other.len() >= self.len() / 8 {
+      unsafe { self.internal_merge_same_mixer(other, f) };
+      return;
+    }
+
+    self.reserve(other.len());
+
+    for (key, value) in other.iter() {
+      match self.entry(key) {
+        Entry::Occupied(mut o) => {
+          f(key, o.get_mut(), value);
+        }
+        Entry::Vacant(v) => {
+          let _: _ = v.insert(value.clone());
+        }
+      }
+    }
+  }
+
+  /// Moves every item from `other` into the map, leaving `other` empty but
+  /// keeping its table. Items from `other` replace items with the same key.
+  ///
+  /// # Panics
+  ///
+  /// Panics when allocation fails.
+
+  pub fn append<B: Allocator>(&mut self, other: &mut HashMapNZ64<T, B>) {
+    self.reserve(other.len());
+
+    for (key, value) in other.drain() {
+      let _: _ = self.insert(key, value);
+    }
+  }
+
+  unsafe fn internal_merge_same_mixer<B: Allocator, F>(&mut self, other: &HashMapNZ64<T, B>, mut f: F)
+  where
+    T: Clone,
+    F: FnMut(NonZeroU64, &mut T, &T)
+  {
+    // Since the mixers match, the stored hashes of the two tables are directly
+    // comparable, and each table holds its items sorted by decreasing hash. So
+    // we can walk both tables in step, visiting the union of their hashes in
+    // decreasing order, and place each item in a new table the same way that
+    // `internal_try_resize_table` does.
+    //
+    // We make three passes. The first counts the distinct hashes to find the
+    // number of regular slots, the second finds where the last item lands to
+    // find the number of extra slots, and the third fills the new table.
+
+    let (a, b) = self.internal_slot_range();
+    let (c, d) = other.internal_slot_range();
+
+    let mut k: usize = 0;
+
+    unsafe { merge_slots(a, b, c, d, |_, _, _| k += 1) };
+
+    let old_t = self.table;
+    let old_u = if old_t.is_null() { 64 - INITIAL_S } else { 64 - self.shift };
+    let old_v =
+      if old_t.is_null() {
+        INITIAL_E.trailing_zeros() as usize
+      } else {
+        (unsafe { self.check.offset_from(old_t) } as usize).trailing_zeros() as usize
+      };
+
+    let new_u = max(old_u, (usize::BITS - (k - 1).leading_zeros()) as usize + 1);
+    let new_s = 64 - new_u;
+    let new_d = 1 << new_u;
+
+    let mut i = 0;
+
+    unsafe { merge_slots(a, b, c, d, |x, _, _| i = max(i, new_d - 1 - spot(new_s, x) as usize) + 1) };
+
+    // The slot after the last item, at index `i`, must be at or before the
+    // final slot, at index `new_d + new_e - 1`.
+
+    let m = (i + 1).saturating_sub(new_d);
+    let new_v = max(old_v, (usize::BITS - m.saturating_sub(1).leading_zeros()) as usize);
+
+    let old_layout = if old_t.is_null() { None } else { table_layout::<T>(old_u, old_v).ok() };
+    let new_layout = match table_layout::<T>(new_u, new_v) {
+      Err(e) => return handle_reserve(Err(e)),
+      Ok(layout) => layout,
+    };
 
-    let old_b_hash = unsafe { &*old_b }.hash;
-    let is_overfull = old_r < 0;
-    let is_overflow = old_b_hash != 0;
+    let new_a = match self.alloc.allocate_zeroed(new_layout) {
+      Err(_) => return handle_reserve(Err(TryReserveError::AllocError { layout: new_layout })),
+      Ok(a) => a.as_ptr() as *mut Slot<T>,
+    };
+
+    let new_c = 1 << (new_u - 1);
+    let new_t = unsafe { new_a.add(new_d - 1) };
+    let new_b = unsafe { new_a.add(new_d + (1 << new_v) - 1) };
 
     // WARNING!
     //
-    // We must be careful to leave the map in a valid state even if attempting
-    // to allocate a new table results in a panic.
-    //
-    // It turns out that the `is_overfull` state with negative space actually
-    // *is* valid, but the `is_overflow` state *is not* valid.
+    // We must be careful to leave the map in a valid state even if a call to
+    // `f` or `clone` panics.
     //
-    // In the latter case, we temporarily remove the item in the final slot and
-    // restore it after we have succeeded at everything that might panic.
+    // Here, we switch the map to the new, empty table before filling it, and
+    // fill it in order of increasing address, updating `self.space` as we go.
+    // As in `internal_clone_slots_from`, the items placed so far are then laid
+    // out exactly as they would be if they were the only items in the map.
     //
-    // This is an instance of the infamous PPYP design pattern.
-
-    if is_overflow {
-      unsafe { &mut *old_b }.hash = 0;
-      self.space = old_r + 1;
+    // Each item of the old table is moved out before anything that might
+    // panic. If something does panic, the items not yet moved are leaked, and
+    // the guard frees the old table either way.
+
+    struct Guard<'a, T, A: Allocator> {
+      alloc: &'a A,
+      table: Option<(NonNull<u8>, Layout)>,
+      var: PhantomData<T>,
     }
 
-    let old_c = 1 << (64 - old_s - 1);
-    let old_d = 1 << (64 - old_s);
-    let old_e = unsafe { old_b.offset_from(old_t) } as usize;
-    let old_n = old_d + old_e;
-    let old_a = unsafe { old_t.sub(old_d - 1) };
-    let old_u = 64 - old_s;
-    let old_v = old_e.trailing_zeros() as usize;
+    impl<'a, T, A: Allocator> Drop for Guard<'a, T, A> {
+      fn drop(&mut self) {
+        if let Some((p, layout)) = self.table {
+          unsafe { self.alloc.deallocate(p, layout) };
+        }
+      }
+    }
 
-    let new_u = old_u + is_overfull as usize;
-    let new_v = old_v + is_overflow as usize;
+    self.table = new_t;
+    self.shift = new_s;
+    self.space = new_c;
+    self.check = new_b;
+    self.epoch = self.epoch.wrapping_add(1);
+    self.grows += (old_layout.map_or(0, |l| l.size()) < new_layout.size()) as u64;
 
-    assert!(new_u <= 64);
-    assert!(new_u <= usize::BITS as usize - 1);
-    assert!(new_v <= usize::BITS as usize - 2);
+    let g: Guard<'_, T, A> = Guard {
+      alloc: &self.alloc,
+      table: old_layout.map(|l| (unsafe { NonNull::new_unchecked(a as *mut u8) }, l)),
+      var: PhantomData,
+    };
 
-    let new_s = 64 - new_u;
-    let new_c = 1 << (64 - new_s - 1);
-    let new_d = 1 << (64 - new_s);
-    let new_e = 1 << new_v;
-    let new_n = new_d + new_e;
-    let new_r = old_r + (new_c - old_c);
+    let rev = self.mixer.invert();
+    let space = &mut self.space;
+    let mut q = new_a;
 
-    assert!(new_n <= isize::MAX as usize / mem::size_of::<Slot<T>>());
+    unsafe {
+      merge_slots(a, b, c, d, |x, p, r| {
+        q = max(q, new_t.offset(- spot(new_s, x)));
+
+        match (p, r) {
+          (Some(p), r) => {
+            (&mut *q).data = MaybeUninit::new((&*p).data.assume_init_read());
+            (&mut *q).hash = x;
+            *space -= 1;
+
+            if let Some(r) = r {
+              f(rev.hash(NonZeroU64::new_unchecked(x)), (&mut *q).data.assume_init_mut(), (&*r).data.assume_init_ref());
+            }
+          }
+          (None, Some(r)) => {
+            (&mut *q).data = MaybeUninit::new((&*r).data.assume_init_ref().clone());
+            (&mut *q).hash = x;
+            *space -= 1;
+          }
+          (None, None) => unreachable!(),
+        }
 
-    let align = mem::align_of::<Slot<T>>();
-    let old_size = old_n * mem::size_of::<Slot<T>>();
-    let new_size = new_n * mem::size_of::<Slot<T>>();
-    let old_layout = unsafe { Layout::from_size_align_unchecked(old_size, align) };
-    let new_layout = unsafe { Layout::from_size_align_unchecked(new_size, align) };
+        q = q.add(1);
+      })
+    };
 
-    let new_a = unsafe { alloc::alloc::alloc_zeroed(new_layout) } as *mut Slot<T>;
-    if new_a.is_null() { match alloc::alloc::handle_alloc_error(new_layout) {} }
+    debug_assert!(q <= new_b);
 
-    // At this point, we know that allocating a new table has succeeded, so we
-    // undo our earlier `if is_overflow { ... }` block.
+    drop(g);
+  }
 
-    if is_overflow {
-      unsafe { &mut *old_b }.hash = old_b_hash;
-      self.space = old_r;
-    }
+  unsafe fn internal_clone_slots_from<B: Allocator>(&mut self, other: &HashMapNZ64<T, B>) where T: Clone {
+    // The map must be empty and have a table with the same mixer and shape as
+    // `other`, so every item can be copied into the same slot it occupies in
+    // `other`.
+    //
+    // WARNING!
+    //
+    // We must be careful to leave the map in a valid state even if a call to
+    // `clone` panics.
+    //
+    // Here, we copy the items in increasing order of address. Each item's slot
+    // depends only on the items before it, so the items copied so far are laid
+    // out exactly as they would be if they were the only items in the map.
+    //
+    // Also, we update `self.space` as we go instead of once at the end.
 
-    let new_t = unsafe { new_a.add(new_d - 1) };
-    let new_b = unsafe { new_a.add(new_n - 1) };
+    let s = self.shift;
+    let d = 1 << (64 - s);
+    let a = unsafe { self.table.sub(d - 1) } as *mut Slot<T>;
+    let b = self.check;
+    let mut p = unsafe { other.table.sub(d - 1) };
+    let mut q = a;
+    let mut k = other.len();
 
-    let mut p = old_a;
-    let mut q = new_a;
+    self.epoch = self.epoch.wrapping_add(1);
 
-    while p <= old_b {
+    while k != 0 {
       let x = unsafe { &*p }.hash;
 
       if x != 0 {
-        q = max(q, unsafe { new_t.offset(- spot(new_s, x)) });
+        let v = unsafe { (&*p).data.assume_init_ref() }.clone();
+        unsafe { &mut *q }.data = MaybeUninit::new(v);
         unsafe { &mut *q }.hash = x;
-        unsafe { &mut *q }.data = MaybeUninit::new(unsafe { (&*p).data.assume_init_read() });
-        q = unsafe { q.add(1) };
+        self.space -= 1;
+        k -= 1;
       }
 
       p = unsafe { p.add(1) };
+      q = unsafe { q.add(1) };
     }
 
-    self.table = new_t;
-    self.shift = new_s;
-    self.space = new_r;
-    self.check = new_b;
+    debug_assert!(q as *const Slot<T> <= b);
+  }
 
-    // The map is now in a valid state, even if `dealloc` panics.
+  /// Removes the given key from the map. Returns the key and its previous
+  /// value, if one was present.
 
-    unsafe { alloc::alloc::dealloc(old_a as *mut u8, old_layout) };
+  #[inline(always)]
+  pub fn remove_entry(&mut self, key: NonZeroU64) -> Option<(NonZeroU64, T)> {
+    Some((key, self.remove(key)?))
   }
 
-  /// Inserts the given key and value into the map. Returns the previous value
-  /// associated with given key, if one was present.
-  ///
-  /// # Panics
-  ///
-  /// Panics when allocation fails. If that happens, it is possible for the map
-  /// to leak an arbitrary set of items, but the map will remain in a valid
-  /// state.
+  /// Removes the given key from the map. Returns the previous value associated
+  /// with the given key, if one was present.
 
   #[inline(always)]
-  pub fn insert(&mut self, key: NonZeroU64, value: T) -> Option<T> {
+  pub fn remove(&mut self, key: NonZeroU64) -> Option<T> {
     let t = self.table as *mut Slot<T>;
 
-    if t.is_null() {
-      unsafe { self.internal_init_table_and_insert(key, value) };
-      return None;
-    }
+    if t.is_null() { return None; }
 
-    let m = self.seeds;
+    let m = self.mixer;
     let s = self.shift;
-    let h = hash(m, key).get();
+    let h = m.hash(key).get();
 
     let mut p = unsafe { t.offset(- spot(s, h)) };
     let mut x = unsafe { &*p }.hash;
@@ -343,44 +1558,57 @@ impl<T> HashMapNZ64<T> {
       x = unsafe { &*p }.hash;
     }
 
-    if x == h {
-      let v = mem::replace(unsafe { (&mut *p).data.assume_init_mut() }, value);
-      return Some(v);
-    }
+    if x != h { return None; }
 
-    let mut v = value;
+    let v = unsafe { self.internal_remove_at(p, false) };
 
-    unsafe { &mut *p }.hash = h;
+    if self.autoshrink { self.internal_autoshrink(); }
 
-    while x != 0 {
-      v = mem::replace(unsafe { (&mut *p).data.assume_init_mut() }, v);
-      p = unsafe { p.add(1) };
-      x = mem::replace(&mut unsafe { &mut *p }.hash, x);
-    }
+    Some(v)
+  }
 
-    unsafe { &mut *p }.data = MaybeUninit::new(v);
+  #[inline(never)]
+  fn internal_autoshrink(&mut self) {
+    // Shrinks the table if it is larger than the initial one and its load has
+    // fallen below one eighth. The shrunk table has a load of at least one
+    // quarter, so at least half of the remaining items must be removed before
+    // the next shrink, and the cost of shrinking amortizes to a constant per
+    // removal.
+    //
+    // Failing to allocate the smaller table isn't an error, so in that case
+    // we just keep the current one.
 
-    let r = self.space - 1;
-    self.space = r;
-    let b = self.check as *mut Slot<T>;
+    let s = self.shift;
 
-    if r < 0 || p == b { unsafe { self.internal_grow_table() }; }
+    if s >= INITIAL_S || 8 * self.len() >= self.internal_num_slots() { return; }
 
-    None
+    let shape =
+      if self.is_empty() {
+        Some((64 - INITIAL_S, INITIAL_E.trailing_zeros() as usize))
+      } else {
+        self.internal_shrunk_shape()
+      };
+
+    if let Some((u, v)) = shape {
+      let _: _ = unsafe { self.internal_try_resize_table(u, v) };
+    }
   }
 
-  /// Removes the given key from the map. Returns the previous value associated
-  /// with the given key, if one was present.
+  /// Removes the given key from the map like [`remove`](Self::remove), and
+  /// then overwrites the vacated storage with zeros, so that no stale copy of
+  /// the value's bytes lingers in the table.
+  ///
+  /// This doesn't zero the returned value itself, which is the caller's
+  /// responsibility.
 
-  #[inline(always)]
-  pub fn remove(&mut self, key: NonZeroU64) -> Option<T> {
+  pub fn remove_zeroize(&mut self, key: NonZeroU64) -> Option<T> {
     let t = self.table as *mut Slot<T>;
 
     if t.is_null() { return None; }
 
-    let m = self.seeds;
+    let m = self.mixer;
     let s = self.shift;
-    let h = hash(m, key).get();
+    let h = m.hash(key).get();
 
     let mut p = unsafe { t.offset(- spot(s, h)) };
     let mut x = unsafe { &*p }.hash;
@@ -392,10 +1620,26 @@ impl<T> HashMapNZ64<T> {
 
     if x != h { return None; }
 
+    Some(unsafe { self.internal_remove_at(p, true) })
+  }
+
+  #[inline(always)]
+  unsafe fn internal_remove_at(&mut self, p: *mut Slot<T>, zeroize: bool) -> T {
+    let t = self.table as *mut Slot<T>;
+    let s = self.shift;
+    let b = self.check as *mut Slot<T>;
+    let mut p = p;
+
     let v = unsafe { (&mut *p).data.assume_init_read() };
 
+    // NB: The final slot is always empty, so every item is before it, and the
+    // loop stops at the final slot at the latest.
+
     loop {
       let q = unsafe { p.add(1) };
+
+      debug_assert!(q <= b);
+
       let x = unsafe { &*q }.hash;
 
       if p < unsafe { t.offset(- spot(s, x)) } || expect(x == 0, false) { break; }
@@ -408,19 +1652,33 @@ impl<T> HashMapNZ64<T> {
 
     unsafe { &mut *p }.hash = 0;
     self.space += 1;
+    self.epoch = self.epoch.wrapping_add(1);
 
-    Some(v)
+    // Shifting items back overwrote every vacated slot except the last one
+    // with a live item, so that is the only slot holding a stale copy.
+    //
+    // Otherwise, in debug builds, we overwrite the now-dead value so that any
+    // stray read of an empty slot produces obviously bogus data.
+
+    if zeroize {
+      unsafe { ptr::write_bytes(ptr::addr_of_mut!((*p).data), 0, 1) };
+    } else if cfg!(debug_assertions) {
+      unsafe { ptr::write_bytes(ptr::addr_of_mut!((*p).data), POISON, 1) };
+    }
+
+    v
   }
 
   #[inline(always)]
-  pub fn entry(&mut self, key: NonZeroU64) -> Entry<'_, T> {
+  pub fn entry(&mut self, key: NonZeroU64) -> Entry<'_, T, A> {
     let t = self.table as *mut Slot<T>;
 
-    if t.is_null() { return Entry::Vacant(VacantEntry { map: self, key }); }
+    let m = self.mixer;
+    let h = m.hash(key).get();
+
+    if t.is_null() { return Entry::Vacant(VacantEntry { map: self, key, hash: h, ptr: t }); }
 
-    let m = self.seeds;
     let s = self.shift;
-    let h = hash(m, key).get();
 
     let mut p = unsafe { t.offset(- spot(s, h)) };
     let mut x = unsafe { &*p }.hash;
@@ -433,7 +1691,50 @@ impl<T> HashMapNZ64<T> {
     if x == h {
       Entry::Occupied(OccupiedEntry { map: self, ptr: p, })
     } else {
-      Entry::Vacant(VacantEntry { map: self, key })
+      Entry::Vacant(VacantEntry { map: self, key, hash: h, ptr: p })
+    }
+  }
+
+  /// Returns a mutable reference to the value associated with the given key,
+  /// inserting the result of `f` if the key is absent.
+
+  #[inline(always)]
+  pub fn get_or_insert_with<F>(&mut self, key: NonZeroU64, f: F) -> &mut T
+  where
+    F: FnOnce() -> T
+  {
+    self.entry(key).or_insert_with(f)
+  }
+
+  /// Returns a mutable reference to the value associated with the given key,
+  /// inserting the result of `f` if the key is absent. If `f` fails, the map
+  /// is left unchanged and the error is returned.
+
+  #[inline(always)]
+  pub fn get_or_try_insert_with<E, F>(&mut self, key: NonZeroU64, f: F) -> Result<&mut T, E>
+  where
+    F: FnOnce() -> Result<T, E>
+  {
+    match self.entry(key) {
+      Entry::Occupied(o) => Ok(o.into_mut()),
+      Entry::Vacant(v) => Ok(v.insert(f()?)),
+    }
+  }
+
+  /// Calls `f` with the entry for each of the given keys in turn.
+  ///
+  /// The table is grown up front by
+  /// [`reserve_for_transaction`](Self::reserve_for_transaction), so inserting
+  /// through the entries never grows it in the middle of the batch.
+
+  pub fn apply_sorted<F>(&mut self, keys: &[NonZeroU64], mut f: F)
+  where
+    F: FnMut(NonZeroU64, Entry<'_, T, A>)
+  {
+    self.reserve_for_transaction(keys.len());
+
+    for &key in keys.iter() {
+      f(key, self.entry(key));
     }
   }
 
@@ -454,43 +1755,76 @@ impl<T> HashMapNZ64<T> {
 
     if k == 0 { return; }
 
-    if mem::needs_drop::<T>() {
-      // WARNING!
-      //
-      // We must be careful to leave the map in a valid state even if a call to
-      // `drop` panics.
-      //
-      // Here, we traverse the table in reverse order to ensure that we don't
-      // remove an item that is currently displacing another item.
-      //
-      // Also, we update `self.space` as we go instead of once at the end.
+    self.epoch = self.epoch.wrapping_add(1);
 
+    // WARNING!
+    //
+    // We must be careful to leave the map in a valid state even if a call to
+    // `drop` panics.
+    //
+    // Here, the guard empties the table when it is dropped, either after we
+    // have dropped every value or while unwinding from a panicking `drop`. In
+    // the latter case, the values not yet dropped are leaked.
+
+    struct Guard<'a, T, A: Allocator> {
+      map: &'a mut HashMapNZ64<T, A>,
+      ptr: (*mut Slot<T>, *mut Slot<T>),
+    }
+
+    impl<'a, T, A: Allocator> Drop for Guard<'a, T, A> {
+      fn drop(&mut self) {
+        let (a, b) = self.ptr;
+        let s = self.map.shift;
+        let mut p = a;
+
+        while p <= b {
+          unsafe { &mut *p }.hash = 0;
+          p = unsafe { p.add(1) };
+        }
+
+        self.map.space = 1 << (64 - s - 1);
+      }
+    }
+
+    let g = Guard { map: self, ptr: (a, b) };
+
+    if mem::needs_drop::<T>() {
       let mut p = b;
       let mut k = k;
-      let mut r = r;
 
       loop {
         p = unsafe { p.sub(1) };
 
-        if unsafe { &mut *p }.hash != 0 {
-          unsafe { &mut *p }.hash = 0;
+        if unsafe { &*p }.hash != 0 {
           k -= 1;
-          r += 1;
-          self.space = r;
           unsafe { (&mut *p).data.assume_init_drop() };
           if k == 0 { break; }
         }
       }
-    } else {
-      let mut p = a;
+    }
 
-      while p <= b {
-        unsafe { &mut *p }.hash = 0;
-        p = unsafe { p.add(1) };
-      }
+    drop(g);
+  }
 
-      self.space = c;
-    }
+  /// Removes every item from the map like [`clear`](Self::clear), and then
+  /// overwrites the whole table with zeros, so that no stale copy of any
+  /// value's bytes lingers in it. Retains heap-allocated memory.
+
+  pub fn clear_zeroize(&mut self) {
+    self.clear();
+
+    let t = self.table as *mut Slot<T>;
+
+    if t.is_null() { return; }
+
+    let s = self.shift;
+    let b = self.check as *mut Slot<T>;
+    let d = 1 << (64 - s);
+    let e = unsafe { b.offset_from(t) } as usize;
+    let n = d + e;
+    let a = unsafe { t.sub(d - 1) };
+
+    unsafe { ptr::write_bytes(a, 0, n) };
   }
 
   /// Removes every item from the map. Releases heap-allocated memory.
@@ -511,6 +1845,7 @@ impl<T> HashMapNZ64<T> {
     self.shift = INITIAL_S;
     self.space = INITIAL_R;
     self.check = ptr::null();
+    self.epoch = self.epoch.wrapping_add(1);
 
     if mem::needs_drop::<T>() {
       // WARNING!
@@ -531,32 +1866,153 @@ impl<T> HashMapNZ64<T> {
       }
     }
 
-    let align = mem::align_of::<Slot<T>>();
-    let size = n * mem::size_of::<Slot<T>>();
-    let layout = unsafe { Layout::from_size_align_unchecked(size, align) };
+    let align = mem::align_of::<Slot<T>>();
+    let size = n * mem::size_of::<Slot<T>>();
+    let layout = unsafe { Layout::from_size_align_unchecked(size, align) };
+
+    unsafe { self.alloc.deallocate(NonNull::new_unchecked(a as *mut u8), layout) };
+  }
+
+  /// Returns whether the longest probe sequence in the table is much longer
+  /// than expected for its number of items. That suggests either an unlucky
+  /// mixer or adversarial keys, and that the map would benefit from being
+  /// rebuilt with a freshly seeded mixer.
+  ///
+  /// This scans the whole table, so it takes time proportional to its size.
+
+  pub fn needs_rehash(&self) -> bool {
+    let k = self.len();
+    let limit = 16 + 2 * (usize::BITS - k.leading_zeros()) as usize;
+    self.internal_max_probe_length() > limit
+  }
+
+  /// Returns a wrapper whose `Debug` implementation prints the map followed by
+  /// some statistics about its table.
+
+  pub fn debug_verbose(&self) -> DebugVerbose<'_, T, A> {
+    DebugVerbose(self)
+  }
+
+  /// Returns an iterator yielding each key and a reference to its associated
+  /// value. The iterator item type is `(NonZeroU64, &'_ T)`.
+  ///
+  /// The items are visited in table order, which is unspecified, and the
+  /// iterator walks the table directly without allocating.
+
+  pub fn iter(&self) -> Iter<'_, T> {
+    let m = self.mixer.invert();
+    let s = self.shift;
+    let r = self.space;
+    let b = self.check;
+    let c = 1 << (64 - s - 1);
+    let k = (c - r) as usize;
+
+    Iter { len: k, ptr: b, rev: m, var: PhantomData }
+  }
+
+  /// Returns an iterator yielding each key and a reference to its associated
+  /// value, in the reverse of the order used by [`iter`](Self::iter). The
+  /// iterator item type is `(NonZeroU64, &'_ T)`.
+
+  pub fn iter_rev(&self) -> IterRev<'_, T> {
+    let m = self.mixer.invert();
+    let t = self.table;
+
+    if t.is_null() { return IterRev { len: 0, ptr: ptr::null(), rev: m, var: PhantomData }; }
+
+    let s = self.shift;
+    let r = self.space;
+    let c = 1 << (64 - s - 1);
+    let d = 1 << (64 - s);
+    let k = (c - r) as usize;
+    let a = unsafe { t.sub(d - 1) };
+
+    IterRev { len: k, ptr: a, rev: m, var: PhantomData }
+  }
+
+  /// Returns the items grouped into `1 << bits` partitions by the top `bits`
+  /// bits of their hashes.
+  ///
+  /// The hashes are already stored in the table, so this doesn't need to
+  /// rehash any keys.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `bits > 16`. Each partition is a separate vector, so more bits
+  /// would allocate an unreasonable number of them.
+
+  pub fn partitioned(&self, bits: u32) -> Box<[Vec<(NonZeroU64, &T)>]> {
+    assert!(bits <= 16, "too many partitions");
+
+    let m = self.mixer.invert();
+    let mut a = (0 .. 1usize << bits).map(|_| Vec::new()).collect::<Box<[_]>>();
+    let mut k = self.len();
+    let mut p = self.check;
+
+    while k != 0 {
+      p = unsafe { p.sub(1) };
+      let x = unsafe { &*p }.hash;
+
+      if x != 0 {
+        let i = x.checked_shr(64 - bits).unwrap_or(0) as usize;
+        let key = m.hash(unsafe { NonZeroU64::new_unchecked(x) });
+        let v = unsafe { (&*p).data.assume_init_ref() };
+        a[i].push((key, v));
+        k -= 1;
+      }
+    }
+
+    a
+  }
+
+  /// Returns all keys, sorted.
 
-    unsafe { alloc::alloc::dealloc(a as *mut u8, layout) };
+  pub fn sorted_keys(&self) -> Box<[NonZeroU64]> {
+    let mut a = Vec::new();
+    self.sorted_keys_into(&mut a);
+    a.into_boxed_slice()
   }
 
-  /// Returns an iterator yielding each key and a reference to its associated
-  /// value. The iterator item type is `(NonZeroU64, &'_ T)`.
+  /// Replaces the contents of the buffer with all keys, sorted. Reusing the
+  /// buffer across calls avoids allocating each time.
 
-  pub fn iter(&self) -> Iter<'_, T> {
-    let m = self.seeds;
-    let s = self.shift;
-    let r = self.space;
-    let b = self.check;
-    let c = 1 << (64 - s - 1);
-    let k = (c - r) as usize;
+  pub fn sorted_keys_into(&self, buf: &mut Vec<NonZeroU64>) {
+    buf.clear();
+    buf.extend(self.keys());
+    buf.sort_unstable();
+  }
 
-    Iter { len: k, ptr: b, rev: m, var: PhantomData }
+  /// Returns all items, sorted by key.
+
+  pub fn items_sorted_by_key(&self) -> Box<[(NonZeroU64, &T)]> {
+    let mut a = Vec::new();
+    self.items_sorted_by_key_into(&mut a);
+    a.into_boxed_slice()
+  }
+
+  /// Replaces the contents of the buffer with all items, sorted by key.
+  /// Reusing the buffer across calls avoids allocating each time.
+
+  pub fn items_sorted_by_key_into<'a>(&'a self, buf: &mut Vec<(NonZeroU64, &'a T)>) {
+    buf.clear();
+    buf.extend(self.iter());
+    buf.sort_unstable_by_key(|x| x.0);
+  }
+
+  /// Returns clones of all items, sorted by key.
+
+  pub fn sorted_entries_owned(&self) -> Vec<(NonZeroU64, T)> where T: Clone {
+    let mut a = self.iter().map(|(k, v)| (k, v.clone())).collect::<Vec<_>>();
+    a.sort_unstable_by_key(|x| x.0);
+    a
   }
 
   /// Returns an iterator yielding each key and a mutable reference to its
-  /// associated value. The iterator item type is `(NonZeroU64, &'_ mut T)`.
+  /// associated value, in the same order as [`iter`](Self::iter). The
+  /// iterator item type is `(NonZeroU64, &'_ mut T)`.
 
   pub fn iter_mut(&mut self) -> IterMut<'_, T> {
-    let m = self.seeds;
+    let m = self.mixer.invert();
     let s = self.shift;
     let r = self.space;
     let b = self.check as *mut Slot<T>;
@@ -568,9 +2024,13 @@ impl<T> HashMapNZ64<T> {
 
   /// Returns an iterator yielding each key. The iterator item type is
   /// `NonZeroU64`.
+  ///
+  /// The keys are yielded lazily in table order, which is unspecified. Unlike
+  /// [`sorted_keys`](Self::sorted_keys), the iterator neither allocates nor
+  /// sorts.
 
   pub fn keys(&self) -> Keys<'_, T> {
-    let m = self.seeds;
+    let m = self.mixer.invert();
     let s = self.shift;
     let r = self.space;
     let b = self.check;
@@ -582,6 +2042,9 @@ impl<T> HashMapNZ64<T> {
 
   /// Returns an iterator yielding a reference to each value. The iterator item
   /// type is `&'_ T`.
+  ///
+  /// Unlike [`iter`](Self::iter), this doesn't need to recover the keys from
+  /// their hashes.
 
   pub fn values(&self) -> Values<'_, T> {
     let s = self.shift;
@@ -606,14 +2069,75 @@ impl<T> HashMapNZ64<T> {
     ValuesMut { len: k, ptr: b, var: PhantomData }
   }
 
+  /// Returns an iterator yielding a mutable reference to each value whose key
+  /// satisfies the predicate. The iterator item type is `&'_ mut T`.
+
+  pub fn values_mut_where<F>(&mut self, pred: F) -> ValuesMutWhere<'_, T, F>
+  where
+    F: Fn(NonZeroU64) -> bool
+  {
+    ValuesMutWhere { iter: self.iter_mut(), pred }
+  }
+
+  /// Returns a parallel iterator yielding each key and a reference to its
+  /// associated value. The iterator item type is `(NonZeroU64, &'_ T)`.
+  ///
+  /// The table is split into contiguous chunks of slots, each of which skips
+  /// its own empty slots.
+
+  #[cfg(feature = "rayon")]
+  pub fn par_iter(&self) -> ParIter<'_, T> where T: Sync {
+    ParIter { ptr: self.internal_slot_range(), rev: self.mixer.invert(), var: PhantomData }
+  }
+
+  /// Returns a parallel iterator yielding a reference to each value. The
+  /// iterator item type is `&'_ T`.
+
+  #[cfg(feature = "rayon")]
+  pub fn par_values(&self) -> ParValues<'_, T> where T: Sync {
+    ParValues { ptr: self.internal_slot_range(), var: PhantomData }
+  }
+
+  fn internal_slot_range(&self) -> (*const Slot<T>, *const Slot<T>) {
+    // Returns the range of slots from the first slot up to but excluding the
+    // final slot, which is always empty.
+
+    let t = self.table;
+
+    if t.is_null() { return (ptr::null(), ptr::null()); }
+
+    let s = self.shift;
+    let d = 1 << (64 - s);
+    let a = unsafe { t.sub(d - 1) };
+
+    (a, self.check)
+  }
+
+  /// Returns an iterator yielding each key and value, removing them from the
+  /// map as it advances. The iterator item type is `(NonZeroU64, T)`.
+  ///
+  /// The map keeps its table, as with [`clear`](Self::clear). If the iterator
+  /// is dropped before it is exhausted, the remaining items are dropped. If
+  /// the iterator is leaked, the map keeps the items not yet yielded.
+
+  pub fn drain(&mut self) -> Drain<'_, T, A> {
+    let m = self.mixer.invert();
+    let b = self.check as *mut Slot<T>;
+
+    if ! self.is_empty() { self.epoch = self.epoch.wrapping_add(1); }
+
+    Drain { map: self, ptr: b, rev: m }
+  }
+
   /// Returns an iterator yielding each value and consuming the map. The
   /// iterator item type is `T`.
 
-  pub fn into_values(self) -> IntoValues<T> {
+  pub fn into_values(self) -> IntoValues<T, A> {
     let o = ManuallyDrop::new(self);
     let t = o.table;
+    let alloc = unsafe { ptr::read(&o.alloc) };
 
-    if t.is_null() { return IntoValues { len: 0, ptr: ptr::null(), mem: (ptr::null_mut(), 0) }; }
+    if t.is_null() { return IntoValues { len: 0, ptr: ptr::null(), mem: (ptr::null_mut(), 0), alloc }; }
 
     let s = o.shift;
     let r = o.space;
@@ -625,7 +2149,51 @@ impl<T> HashMapNZ64<T> {
     let k = (c - r) as usize;
     let a = unsafe { t.sub(d - 1) } as *mut u8;
 
-    IntoValues { len: k, ptr: b, mem: (a, n * mem::size_of::<Slot<T>>()) }
+    IntoValues { len: k, ptr: b, mem: (a, n * mem::size_of::<Slot<T>>()), alloc }
+  }
+
+  /// Returns an iterator yielding each key present in both maps together with
+  /// references to its associated values. The iterator item type is
+  /// `(NonZeroU64, &'_ T, &'_ U)`.
+  ///
+  /// The smaller of the two maps is iterated and the larger is probed.
+
+  pub fn join<'a, U, B: Allocator>(&'a self, other: &'a HashMapNZ64<U, B>) -> Join<'a, T, U, A, B> {
+    if self.len() <= other.len() {
+      Join { inner: JoinInner::Left(self.iter(), other) }
+    } else {
+      Join { inner: JoinInner::Right(self, other.iter()) }
+    }
+  }
+
+  /// Returns an iterator yielding each key in this map together with a
+  /// reference to its associated value and, if present, a reference to its
+  /// associated value in `other`. The iterator item type is
+  /// `(NonZeroU64, &'_ T, Option<&'_ U>)`.
+
+  pub fn left_join<'a, U, B: Allocator>(&'a self, other: &'a HashMapNZ64<U, B>) -> LeftJoin<'a, T, U, B> {
+    LeftJoin { iter: self.iter(), other }
+  }
+
+  /// Returns an iterator yielding each key present in either map together
+  /// with references to its associated values in each map, if present. Each
+  /// key is yielded exactly once. The iterator item type is
+  /// `(NonZeroU64, Option<&'_ T>, Option<&'_ U>)`.
+
+  pub fn full_join<'a, U, B: Allocator>(&'a self, other: &'a HashMapNZ64<U, B>) -> FullJoin<'a, T, U, A, B> {
+    FullJoin { left: self.iter(), right: other.iter(), this: self, other }
+  }
+
+  /// Returns an iterator yielding each key in `keys` that is present in the
+  /// map together with a reference to its associated value, in the order that
+  /// the keys appear in `keys`. The iterator item type is
+  /// `(NonZeroU64, &'_ T)`.
+  ///
+  /// This is convenient for joining against a sorted list of keys, although
+  /// the keys needn't actually be sorted.
+
+  pub fn iter_matching<'a>(&'a self, keys: &'a [NonZeroU64]) -> Matching<'a, T, A> {
+    Matching { map: self, keys: keys.iter() }
   }
 
   fn internal_num_slots(&self) -> usize {
@@ -654,6 +2222,82 @@ impl<T> HashMapNZ64<T> {
     (k as f64) / (n as f64)
   }
 
+  fn internal_max_probe_length(&self) -> usize {
+    let t = self.table;
+    let s = self.shift;
+    let mut k = self.len();
+    let mut p = self.check;
+    let mut m = 0;
+
+    while k != 0 {
+      p = unsafe { p.sub(1) };
+      let x = unsafe { &*p }.hash;
+
+      if x != 0 {
+        let i = unsafe { p.offset_from(t.offset(- spot(s, x))) } as usize;
+        m = max(m, i);
+        k -= 1;
+      }
+    }
+
+    m
+  }
+
+  fn internal_probe_length_histogram(&self) -> Box<[usize]> {
+    let mut a = vec![0; self.internal_max_probe_length() + 1];
+    let t = self.table;
+    let s = self.shift;
+    let mut k = self.len();
+    let mut p = self.check;
+
+    while k != 0 {
+      p = unsafe { p.sub(1) };
+      let x = unsafe { &*p }.hash;
+
+      if x != 0 {
+        let i = unsafe { p.offset_from(t.offset(- spot(s, x))) } as usize;
+        a[i] += 1;
+        k -= 1;
+      }
+    }
+
+    a.into_boxed_slice()
+  }
+
+  fn internal_report(&self) -> internal::Report {
+    let t = self.table;
+    let s = self.shift;
+    let k = self.len();
+    let mut p = self.check;
+    let mut i = k;
+    let mut m = 0;
+    let mut z = 0;
+
+    while i != 0 {
+      p = unsafe { p.sub(1) };
+      let x = unsafe { &*p }.hash;
+
+      if x != 0 {
+        let j = unsafe { p.offset_from(t.offset(- spot(s, x))) } as usize;
+        m = max(m, j);
+        z += j;
+        i -= 1;
+      }
+    }
+
+    internal::Report {
+      len: k,
+      capacity: self.capacity(),
+      num_slots: self.internal_num_slots(),
+      num_bytes: self.internal_num_bytes(),
+      load: self.internal_load(),
+      is_allocated: ! t.is_null(),
+      max_probe: m,
+      mean_probe: if k == 0 { 0. } else { (z as f64) / (k as f64) },
+      grow_count: self.grows,
+    }
+  }
+
   fn internal_allocation_info(&self) -> Option<(NonNull<u8>, Layout)> {
     let t = self.table;
 
@@ -674,41 +2318,223 @@ impl<T> HashMapNZ64<T> {
   }
 }
 
-impl<T> Drop for HashMapNZ64<T> {
+impl<T: ?Sized, A: Allocator> HashMapNZ64<Box<T>, A> {
+  /// Returns a reference to the boxed value associated with the given key, if
+  /// present.
+  ///
+  /// This is intended for maps of trait objects, `HashMapNZ64<Box<dyn Trait>>`,
+  /// where it saves dereferencing the box at every call site.
+
+  #[inline(always)]
+  pub fn get_dyn(&self, key: NonZeroU64) -> Option<&T> {
+    self.get(key).map(|x| &**x)
+  }
+
+  /// Returns a mutable reference to the boxed value associated with the given
+  /// key, if present.
+
+  #[inline(always)]
+  pub fn get_dyn_mut(&mut self, key: NonZeroU64) -> Option<&mut T> {
+    self.get_mut(key).map(|x| &mut **x)
+  }
+}
+
+impl<A: Allocator> HashMapNZ64<u64, A> {
+  /// Returns the sum of the values, wrapping on overflow.
+
+  pub fn value_sum(&self) -> u64 {
+    self.values().fold(0, |a, &x| a.wrapping_add(x))
+  }
+
+  /// Returns the mean of the values, or `None` if the map is empty.
+
+  pub fn value_mean(&self) -> Option<f64> {
+    let k = self.len();
+
+    if k == 0 { return None; }
+
+    let a = self.values().fold(0u128, |a, &x| a + x as u128);
+
+    Some(a as f64 / k as f64)
+  }
+}
+
+impl<A: Allocator> HashMapNZ64<f64, A> {
+  /// Returns the sum of the values.
+
+  pub fn value_sum(&self) -> f64 {
+    self.values().fold(0., |a, &x| a + x)
+  }
+
+  /// Returns the mean of the values, or `None` if the map is empty.
+
+  pub fn value_mean(&self) -> Option<f64> {
+    let k = self.len();
+
+    if k == 0 { return None; }
+
+    Some(self.value_sum() / k as f64)
+  }
+}
+
+impl<T> Default for HashMapNZ64<T> {
+  #[inline(always)]
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<T: Clone, A: Allocator + Clone> Clone for HashMapNZ64<T, A> {
+  fn clone(&self) -> Self {
+    let mut t = Self::new_with_mixer_in(self.mixer, self.alloc.clone());
+    t.autoshrink = self.autoshrink;
+
+    if ! self.table.is_null() {
+      let s = self.shift;
+      let e = unsafe { self.check.offset_from(self.table) } as usize;
+      unsafe { t.internal_init_table(64 - s, e.trailing_zeros() as usize) };
+      unsafe { t.internal_clone_slots_from(self) };
+    }
+
+    t
+  }
+
+  /// When both maps have the same mixer and the same number of slots, this
+  /// reuses the allocation of `self` and copies each item into the same slot
+  /// that it occupies in `source`, without probing.
+
+  fn clone_from(&mut self, source: &Self) {
+    self.clear();
+    self.autoshrink = source.autoshrink;
+
+    if self.mixer == source.mixer
+      && ! self.table.is_null()
+      && ! source.table.is_null()
+      && self.shift == source.shift
+      && self.internal_num_slots() == source.internal_num_slots()
+    {
+      unsafe { self.internal_clone_slots_from(source) };
+    } else {
+      self.mixer = source.mixer;
+      self.extend_from_map(source);
+    }
+  }
+}
+
+/// Two maps are equal when they contain the same keys with equal values,
+/// regardless of their mixers or table layouts.
+
+impl<T: PartialEq, A: Allocator, B: Allocator> PartialEq<HashMapNZ64<T, B>> for HashMapNZ64<T, A> {
+  fn eq(&self, other: &HashMapNZ64<T, B>) -> bool {
+    self.len() == other.len() && self.iter().all(|(k, v)| other.get(k) == Some(v))
+  }
+}
+
+impl<T: Eq, A: Allocator> Eq for HashMapNZ64<T, A> {}
+
+impl<T, A: Allocator> Drop for HashMapNZ64<T, A> {
   fn drop(&mut self) {
     self.reset()
   }
 }
 
-impl<T> Index<NonZeroU64> for HashMapNZ64<T> {
+impl<T, A: Allocator> Index<NonZeroU64> for HashMapNZ64<T, A> {
   type Output = T;
 
   #[inline(always)]
   fn index(&self, key: NonZeroU64) -> &T {
-    self.get(key).unwrap()
+    match self.get(key) {
+      None => panic!("no entry found for key {}", key),
+      Some(value) => value,
+    }
   }
 }
 
-impl<T> IndexMut<NonZeroU64> for HashMapNZ64<T> {
+impl<T, A: Allocator> IndexMut<NonZeroU64> for HashMapNZ64<T, A> {
   #[inline(always)]
   fn index_mut(&mut self, key: NonZeroU64) -> &mut T {
-    self.get_mut(key).unwrap()
+    match self.get_mut(key) {
+      None => panic!("no entry found for key {}", key),
+      Some(value) => value,
+    }
+  }
+}
+
+/// Creates a map from an array of items. Later duplicate keys overwrite
+/// earlier ones.
+///
+/// ```
+/// # use core::num::NonZeroU64;
+/// # use wordmap::map::HashMapNZ64;
+/// let k = |i| NonZeroU64::new(i).unwrap();
+/// let t = HashMapNZ64::from([(k(1), "a"), (k(2), "b"), (k(1), "c")]);
+/// assert_eq!(t.len(), 2);
+/// assert_eq!(t[k(1)], "c");
+/// ```
+
+impl<T, const N: usize> From<[(NonZeroU64, T); N]> for HashMapNZ64<T> {
+  fn from(items: [(NonZeroU64, T); N]) -> Self {
+    Self::from_iter(items)
+  }
+}
+
+impl<T> FromIterator<(NonZeroU64, T)> for HashMapNZ64<T> {
+  fn from_iter<I: IntoIterator<Item = (NonZeroU64, T)>>(iter: I) -> Self {
+    let mut t = Self::new();
+    t.extend(iter);
+    t
+  }
+}
+
+impl<T, A: Allocator> Extend<(NonZeroU64, T)> for HashMapNZ64<T, A> {
+  fn extend<I: IntoIterator<Item = (NonZeroU64, T)>>(&mut self, iter: I) {
+    let iter = iter.into_iter();
+
+    // The iterator may contain duplicate keys, so we only reserve for the
+    // lower bound, which might still overestimate the number of new keys.
+
+    self.reserve(iter.size_hint().0);
+
+    for (key, value) in iter {
+      let _: _ = self.insert(key, value);
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl<T, A: Allocator, S> From<HashMapNZ64<T, A>> for std::collections::HashMap<NonZeroU64, T, S>
+where
+  S: core::hash::BuildHasher + Default
+{
+  fn from(t: HashMapNZ64<T, A>) -> Self {
+    let mut u = Self::with_capacity_and_hasher(t.len(), S::default());
+    u.extend(t);
+    u
+  }
+}
+
+#[cfg(feature = "std")]
+impl<T, S> From<std::collections::HashMap<NonZeroU64, T, S>> for HashMapNZ64<T> {
+  fn from(u: std::collections::HashMap<NonZeroU64, T, S>) -> Self {
+    Self::from_iter(u)
   }
 }
 
-pub struct OccupiedEntry<'a, T: 'a> {
-  map: &'a mut HashMapNZ64<T>,
+pub struct OccupiedEntry<'a, T: 'a, A: Allocator = Global> {
+  map: &'a mut HashMapNZ64<T, A>,
   ptr: *mut Slot<T>,
 }
 
-pub struct VacantEntry<'a, T: 'a> {
-  map: &'a mut HashMapNZ64<T>,
+pub struct VacantEntry<'a, T: 'a, A: Allocator = Global> {
+  map: &'a mut HashMapNZ64<T, A>,
   key: NonZeroU64,
+  hash: u64,
+  ptr: *mut Slot<T>, // null if no table is allocated
 }
 
-pub enum Entry<'a, T: 'a> {
-  Occupied(OccupiedEntry<'a, T>),
-  Vacant(VacantEntry<'a, T>),
+pub enum Entry<'a, T: 'a, A: Allocator = Global> {
+  Occupied(OccupiedEntry<'a, T, A>),
+  Vacant(VacantEntry<'a, T, A>),
 }
 
 /// Iterator returned by [`HashMapNZ64::iter`].
@@ -717,7 +2543,17 @@ pub enum Entry<'a, T: 'a> {
 pub struct Iter<'a, T: 'a> {
   len: usize,
   ptr: *const Slot<T>,
-  rev: Seeds,
+  rev: Mixer,
+  var: PhantomData<&'a T>,
+}
+
+/// Iterator returned by [`HashMapNZ64::iter_rev`].
+
+#[derive(Clone)]
+pub struct IterRev<'a, T: 'a> {
+  len: usize,
+  ptr: *const Slot<T>,
+  rev: Mixer,
   var: PhantomData<&'a T>,
 }
 
@@ -726,7 +2562,7 @@ pub struct Iter<'a, T: 'a> {
 pub struct IterMut<'a, T: 'a> {
   len: usize,
   ptr: *mut Slot<T>,
-  rev: Seeds,
+  rev: Mixer,
   var: PhantomData<&'a mut T>,
 }
 
@@ -736,7 +2572,7 @@ pub struct IterMut<'a, T: 'a> {
 pub struct Keys<'a, T: 'a> {
   len: usize,
   ptr: *const Slot<T>,
-  rev: Seeds,
+  rev: Mixer,
   var: PhantomData<&'a T>,
 }
 
@@ -749,33 +2585,173 @@ pub struct Values<'a, T: 'a> {
   var: PhantomData<&'a T>,
 }
 
-/// Iterator returned by [`HashMapNZ64::values_mut`].
+/// Iterator returned by [`HashMapNZ64::values_mut`].
+
+pub struct ValuesMut<'a, T: 'a> {
+  len: usize,
+  ptr: *mut Slot<T>,
+  var: PhantomData<&'a mut T>,
+}
+
+/// Iterator returned by [`HashMapNZ64::values_mut_where`].
+
+pub struct ValuesMutWhere<'a, T: 'a, F> {
+  iter: IterMut<'a, T>,
+  pred: F,
+}
+
+/// Parallel iterator returned by [`HashMapNZ64::par_iter`].
+///
+/// The items aren't spread evenly over the slots, so a split point can't be
+/// mapped to an item index, and this doesn't implement
+/// `IndexedParallelIterator`. Consumers that need an exact length, such as
+/// `collect::<Vec<_>>()`, fall back to collecting chunks and concatenating.
+
+#[cfg(feature = "rayon")]
+pub struct ParIter<'a, T: 'a> {
+  ptr: (*const Slot<T>, *const Slot<T>),
+  rev: Mixer,
+  var: PhantomData<&'a T>,
+}
+
+/// Parallel iterator returned by [`HashMapNZ64::par_values`].
+
+#[cfg(feature = "rayon")]
+pub struct ParValues<'a, T: 'a> {
+  ptr: (*const Slot<T>, *const Slot<T>),
+  var: PhantomData<&'a T>,
+}
+
+// A chunk of slots, yielding the stored hash and value of each item.
+
+#[cfg(feature = "rayon")]
+struct ParSlots<'a, T: 'a> {
+  ptr: (*const Slot<T>, *const Slot<T>),
+  var: PhantomData<&'a T>,
+}
+
+/// Iterator returned by [`HashMapNZ64::into_iter`].
+
+pub struct IntoIter<T, A: Allocator = Global> {
+  rev: Mixer,
+  len: usize,
+  ptr: *const Slot<T>, // covariant in `T`
+  mem: (*mut u8, usize),
+  alloc: A,
+}
+
+/// Iterator returned by [`HashMapNZ64::drain`].
+
+pub struct Drain<'a, T: 'a, A: Allocator = Global> {
+  map: &'a mut HashMapNZ64<T, A>,
+  ptr: *mut Slot<T>,
+  rev: Mixer,
+}
+
+/// Iterator returned by [`HashMapNZ64::into_values`].
+
+pub struct IntoValues<T, A: Allocator = Global> {
+  len: usize,
+  ptr: *const Slot<T>, // covariant in `T`
+  mem: (*mut u8, usize),
+  alloc: A,
+}
+
+/// Iterator returned by [`HashMapNZ64::join`].
+
+pub struct Join<'a, T: 'a, U: 'a, A: Allocator = Global, B: Allocator = Global> {
+  inner: JoinInner<'a, T, U, A, B>,
+}
+
+enum JoinInner<'a, T: 'a, U: 'a, A: Allocator, B: Allocator> {
+  Left(Iter<'a, T>, &'a HashMapNZ64<U, B>),
+  Right(&'a HashMapNZ64<T, A>, Iter<'a, U>),
+}
+
+/// Iterator returned by [`HashMapNZ64::left_join`].
+
+pub struct LeftJoin<'a, T: 'a, U: 'a, B: Allocator = Global> {
+  iter: Iter<'a, T>,
+  other: &'a HashMapNZ64<U, B>,
+}
+
+/// Iterator returned by [`HashMapNZ64::full_join`].
+
+pub struct FullJoin<'a, T: 'a, U: 'a, A: Allocator = Global, B: Allocator = Global> {
+  left: Iter<'a, T>,
+  right: Iter<'a, U>,
+  this: &'a HashMapNZ64<T, A>,
+  other: &'a HashMapNZ64<U, B>,
+}
+
+/// Iterator returned by [`HashMapNZ64::iter_matching`].
+
+pub struct Matching<'a, T: 'a, A: Allocator = Global> {
+  map: &'a HashMapNZ64<T, A>,
+  keys: core::slice::Iter<'a, NonZeroU64>,
+}
+
+unsafe impl<'a, T: Sync> Send for Iter<'a, T> {}
+
+unsafe impl<'a, T: Sync> Sync for Iter<'a, T> {}
+
+unsafe impl<'a, T: Sync> Send for IterRev<'a, T> {}
+
+unsafe impl<'a, T: Sync> Sync for IterRev<'a, T> {}
+
+unsafe impl<'a, T: Send> Send for IterMut<'a, T> {}
+
+unsafe impl<'a, T: Sync> Sync for IterMut<'a, T> {}
+
+unsafe impl<'a, T: Sync> Send for Keys<'a, T> {}
+
+unsafe impl<'a, T: Sync> Sync for Keys<'a, T> {}
+
+unsafe impl<'a, T: Sync> Send for Values<'a, T> {}
+
+unsafe impl<'a, T: Sync> Sync for Values<'a, T> {}
+
+unsafe impl<'a, T: Send> Send for ValuesMut<'a, T> {}
+
+unsafe impl<'a, T: Sync> Sync for ValuesMut<'a, T> {}
+
+unsafe impl<T: Send, A: Allocator + Send> Send for IntoIter<T, A> {}
+
+unsafe impl<T: Sync, A: Allocator + Sync> Sync for IntoIter<T, A> {}
+
+unsafe impl<T: Send, A: Allocator + Send> Send for IntoValues<T, A> {}
+
+unsafe impl<'a, T: Send, A: Allocator + Send> Send for Drain<'a, T, A> {}
+
+unsafe impl<'a, T: Sync, A: Allocator + Sync> Sync for Drain<'a, T, A> {}
+
+unsafe impl<T: Sync, A: Allocator + Sync> Sync for IntoValues<T, A> {}
 
-pub struct ValuesMut<'a, T: 'a> {
-  len: usize,
-  ptr: *mut Slot<T>,
-  var: PhantomData<&'a mut T>,
-}
+#[cfg(feature = "rayon")]
+unsafe impl<'a, T: Sync> Send for ParIter<'a, T> {}
 
-/// Iterator returned by [`HashMapNZ64::into_iter`].
+#[cfg(feature = "rayon")]
+unsafe impl<'a, T: Sync> Send for ParValues<'a, T> {}
 
-pub struct IntoIter<T> {
-  rev: Seeds,
-  len: usize,
-  ptr: *const Slot<T>, // covariant in `T`
-  mem: (*mut u8, usize),
-}
+#[cfg(feature = "rayon")]
+unsafe impl<'a, T: Sync> Send for ParSlots<'a, T> {}
 
-/// Iterator returned by [`HashMapNZ64::into_values`].
+unsafe impl<'a, T: Send, A: Allocator + Send> Send for OccupiedEntry<'a, T, A> {}
 
-pub struct IntoValues<T> {
-  len: usize,
-  ptr: *const Slot<T>, // covariant in `T`
-  mem: (*mut u8, usize),
-}
+unsafe impl<'a, T: Sync, A: Allocator + Sync> Sync for OccupiedEntry<'a, T, A> {}
+
+unsafe impl<'a, T: Send, A: Allocator + Send> Send for VacantEntry<'a, T, A> {}
+
+unsafe impl<'a, T: Sync, A: Allocator + Sync> Sync for VacantEntry<'a, T, A> {}
 
 impl<'a, T> FusedIterator for Iter<'a, T> {}
 
+impl<'a, T, A: Allocator> FusedIterator for Drain<'a, T, A> {}
+
+impl<'a, T, A: Allocator> ExactSizeIterator for Drain<'a, T, A> {}
+
+impl<'a, T> FusedIterator for IterRev<'a, T> {}
+
 impl<'a, T> FusedIterator for IterMut<'a, T> {}
 
 impl<'a, T> FusedIterator for Keys<'a, T> {}
@@ -784,8 +2760,20 @@ impl<'a, T> FusedIterator for Values<'a, T> {}
 
 impl<'a, T> FusedIterator for ValuesMut<'a, T> {}
 
+impl<'a, T, F: Fn(NonZeroU64) -> bool> FusedIterator for ValuesMutWhere<'a, T, F> {}
+
+impl<'a, T, U, A: Allocator, B: Allocator> FusedIterator for Join<'a, T, U, A, B> {}
+
+impl<'a, T, U, B: Allocator> FusedIterator for LeftJoin<'a, T, U, B> {}
+
+impl<'a, T, U, A: Allocator, B: Allocator> FusedIterator for FullJoin<'a, T, U, A, B> {}
+
+impl<'a, T, A: Allocator> FusedIterator for Matching<'a, T, A> {}
+
 impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
 
+impl<'a, T> ExactSizeIterator for IterRev<'a, T> {}
+
 impl<'a, T> ExactSizeIterator for IterMut<'a, T> {}
 
 impl<'a, T> ExactSizeIterator for Keys<'a, T> {}
@@ -794,17 +2782,20 @@ impl<'a, T> ExactSizeIterator for Values<'a, T> {}
 
 impl<'a, T> ExactSizeIterator for ValuesMut<'a, T> {}
 
-impl<T> IntoIterator for HashMapNZ64<T> {
+impl<'a, T, U, B: Allocator> ExactSizeIterator for LeftJoin<'a, T, U, B> {}
+
+impl<T, A: Allocator> IntoIterator for HashMapNZ64<T, A> {
   type Item = (NonZeroU64, T);
 
-  type IntoIter = IntoIter<T>;
+  type IntoIter = IntoIter<T, A>;
 
-  fn into_iter(self) -> IntoIter<T> {
+  fn into_iter(self) -> IntoIter<T, A> {
     let o = ManuallyDrop::new(self);
-    let m = o.seeds;
+    let m = o.mixer.invert();
     let t = o.table;
+    let alloc = unsafe { ptr::read(&o.alloc) };
 
-    if t.is_null() { return IntoIter { rev: m, len: 0, ptr: ptr::null(), mem: (ptr::null_mut(), 0) }; }
+    if t.is_null() { return IntoIter { rev: m, len: 0, ptr: ptr::null(), mem: (ptr::null_mut(), 0), alloc }; }
 
     let s = o.shift;
     let r = o.space;
@@ -816,11 +2807,11 @@ impl<T> IntoIterator for HashMapNZ64<T> {
     let k = (c - r) as usize;
     let a = unsafe { t.sub(d - 1) } as *mut u8;
 
-    IntoIter { rev: m, len: k, ptr: b, mem: (a, n * mem::size_of::<Slot<T>>()) }
+    IntoIter { rev: m, len: k, ptr: b, mem: (a, n * mem::size_of::<Slot<T>>()), alloc }
   }
 }
 
-impl<T: fmt::Debug> fmt::Debug for HashMapNZ64<T> {
+impl<T: fmt::Debug, A: Allocator> fmt::Debug for HashMapNZ64<T, A> {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
     let mut items = self.iter().collect::<Vec<(NonZeroU64, &T)>>();
 
@@ -836,7 +2827,28 @@ impl<T: fmt::Debug> fmt::Debug for HashMapNZ64<T> {
   }
 }
 
-impl<'a, T> OccupiedEntry<'a, T> {
+/// Wrapper returned by [`HashMapNZ64::debug_verbose`].
+
+pub struct DebugVerbose<'a, T: 'a, A: Allocator = Global>(&'a HashMapNZ64<T, A>);
+
+impl<'a, T: fmt::Debug, A: Allocator> fmt::Debug for DebugVerbose<'a, T, A> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+    let t = self.0;
+
+    fmt::Debug::fmt(t, f)?;
+
+    write!(
+      f,
+      "{}len = {}, load = {:.3}, max probe = {}",
+      if f.alternate() { "\n" } else { " # " },
+      t.len(),
+      t.internal_load(),
+      t.internal_max_probe_length(),
+    )
+  }
+}
+
+impl<'a, T, A: Allocator> OccupiedEntry<'a, T, A> {
   #[inline(always)]
   pub fn get(&self) -> &T {
     unsafe { (&*self.ptr).data.assume_init_ref() }
@@ -859,39 +2871,75 @@ impl<'a, T> OccupiedEntry<'a, T> {
 
   #[inline(always)]
   pub fn remove(self) -> T {
-    let mut p = self.ptr;
-    let o = self.map;
-    let t = o.table as *mut Slot<T>;
-    let s = o.shift;
+    unsafe { self.map.internal_remove_at(self.ptr, false) }
+  }
+}
 
-    let v = unsafe { (&mut *p).data.assume_init_read() };
 
-    loop {
-      let q = unsafe { p.add(1) };
-      let x = unsafe { &*q }.hash;
+impl<'a, T, A: Allocator> VacantEntry<'a, T, A> {
+  #[inline(always)]
+  pub fn key(&self) -> NonZeroU64 {
+    self.key
+  }
 
-      if p < unsafe { t.offset(- spot(s, x)) } || expect(x == 0, false) { break; }
+  #[inline(always)]
+  pub fn insert(self, value: T) -> &'a mut T {
+    let h = self.hash;
+    let p = self.ptr;
+    let m = self.map;
+
+    let p =
+      if p.is_null() {
+        unsafe { m.internal_init_table_and_insert(h, value) };
+        unsafe { m.internal_probe(h) }
+      } else {
+        unsafe { m.internal_insert_at(p, h, value) }
+      };
+
+    unsafe { (&mut *p).data.assume_init_mut() }
+  }
+}
 
-      unsafe { &mut *p }.hash = x;
-      unsafe { &mut *p }.data = MaybeUninit::new(unsafe { (&*q).data.assume_init_read() });
+impl<'a, T, A: Allocator> Entry<'a, T, A> {
+  /// Returns a mutable reference to the value, inserting the given value if
+  /// the entry is vacant.
 
-      p = q;
+  #[inline(always)]
+  pub fn or_insert(self, value: T) -> &'a mut T {
+    match self {
+      Entry::Occupied(o) => o.into_mut(),
+      Entry::Vacant(v) => v.insert(value),
     }
+  }
 
-    unsafe { &mut *p }.hash = 0;
-    o.space += 1;
+  /// Returns a mutable reference to the value, inserting the result of `f` if
+  /// the entry is vacant.
 
-    v
+  #[inline(always)]
+  pub fn or_insert_with<F>(self, f: F) -> &'a mut T
+  where
+    F: FnOnce() -> T
+  {
+    match self {
+      Entry::Occupied(o) => o.into_mut(),
+      Entry::Vacant(v) => v.insert(f()),
+    }
   }
-}
-
 
-impl<'a, T> VacantEntry<'a, T> {
-  pub fn insert(self, value: T) -> &'a mut T {
-    // TODO: make this efficient
+  /// Calls `f` with the value if the entry is occupied, and returns the entry.
 
-    let _: _ = self.map.insert(self.key, value);
-    self.map.get_mut(self.key).unwrap()
+  #[inline(always)]
+  pub fn and_modify<F>(self, f: F) -> Self
+  where
+    F: FnOnce(&mut T)
+  {
+    match self {
+      Entry::Occupied(mut o) => {
+        f(o.get_mut());
+        Entry::Occupied(o)
+      }
+      Entry::Vacant(v) => Entry::Vacant(v),
+    }
   }
 }
 
@@ -912,7 +2960,7 @@ impl<'a, T> Iterator for Iter<'a, T> {
       x = unsafe { &*p }.hash;
     }
 
-    let x = hash(self.rev, unsafe { NonZeroU64::new_unchecked(x) });
+    let x = self.rev.hash(unsafe { NonZeroU64::new_unchecked(x) });
     let v = unsafe { (&*p).data.assume_init_ref() };
 
     self.len = k - 1;
@@ -927,6 +2975,38 @@ impl<'a, T> Iterator for Iter<'a, T> {
   }
 }
 
+impl<'a, T> Iterator for IterRev<'a, T> {
+  type Item = (NonZeroU64, &'a T);
+
+  #[inline(always)]
+  fn next(&mut self) -> Option<Self::Item> {
+    let k = self.len;
+
+    if k == 0 { return None; }
+
+    let mut p = self.ptr;
+    let mut x = unsafe { &*p }.hash;
+
+    while x == 0 {
+      p = unsafe { p.add(1) };
+      x = unsafe { &*p }.hash;
+    }
+
+    let x = self.rev.hash(unsafe { NonZeroU64::new_unchecked(x) });
+    let v = unsafe { (&*p).data.assume_init_ref() };
+
+    self.len = k - 1;
+    self.ptr = unsafe { p.add(1) };
+
+    Some((x, v))
+  }
+
+  #[inline(always)]
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (self.len, Some(self.len))
+  }
+}
+
 impl<'a, T> Iterator for IterMut<'a, T> {
   type Item = (NonZeroU64, &'a mut T);
 
@@ -944,7 +3024,7 @@ impl<'a, T> Iterator for IterMut<'a, T> {
       x = unsafe { &*p }.hash;
     }
 
-    let x = hash(self.rev, unsafe { NonZeroU64::new_unchecked(x) });
+    let x = self.rev.hash(unsafe { NonZeroU64::new_unchecked(x) });
     let v = unsafe { (&mut *p).data.assume_init_mut() };
 
     self.len = k - 1;
@@ -976,7 +3056,7 @@ impl<'a, T> Iterator for Keys<'a, T> {
       x = unsafe { &*p }.hash;
     }
 
-    let x = hash(self.rev, unsafe { NonZeroU64::new_unchecked(x) });
+    let x = self.rev.hash(unsafe { NonZeroU64::new_unchecked(x) });
 
     self.len = k - 1;
     self.ptr = p;
@@ -1052,7 +3132,24 @@ impl<'a, T> Iterator for ValuesMut<'a, T> {
   }
 }
 
-impl<T> Iterator for IntoIter<T> {
+impl<'a, T, F: Fn(NonZeroU64) -> bool> Iterator for ValuesMutWhere<'a, T, F> {
+  type Item = &'a mut T;
+
+  #[inline(always)]
+  fn next(&mut self) -> Option<Self::Item> {
+    loop {
+      let (k, v) = self.iter.next()?;
+      if (self.pred)(k) { return Some(v); }
+    }
+  }
+
+  #[inline(always)]
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (0, Some(self.iter.len()))
+  }
+}
+
+impl<T, A: Allocator> Iterator for IntoIter<T, A> {
   type Item = (NonZeroU64, T);
 
   #[inline(always)]
@@ -1069,7 +3166,7 @@ impl<T> Iterator for IntoIter<T> {
       x = unsafe { &*p }.hash;
     }
 
-    let x = hash(self.rev, unsafe { NonZeroU64::new_unchecked(x) });
+    let x = self.rev.hash(unsafe { NonZeroU64::new_unchecked(x) });
     let v = unsafe { (&*p).data.assume_init_read() };
 
     self.len = k - 1;
@@ -1084,7 +3181,7 @@ impl<T> Iterator for IntoIter<T> {
   }
 }
 
-impl<T> Drop for IntoIter<T> {
+impl<T, A: Allocator> Drop for IntoIter<T, A> {
   fn drop(&mut self) {
     for (_, v) in &mut *self { drop::<T>(v) }
 
@@ -1093,12 +3190,53 @@ impl<T> Drop for IntoIter<T> {
       let align = mem::align_of::<Slot<T>>();
       let layout = unsafe { Layout::from_size_align_unchecked(size, align) };
 
-      unsafe { alloc::alloc::dealloc(self.mem.0, layout) };
+      unsafe { self.alloc.deallocate(NonNull::new_unchecked(self.mem.0), layout) };
+    }
+  }
+}
+
+impl<'a, T, A: Allocator> Iterator for Drain<'a, T, A> {
+  type Item = (NonZeroU64, T);
+
+  #[inline(always)]
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.map.is_empty() { return None; }
+
+    // As in `clear`, we traverse the table in reverse order and update the
+    // space as we go, so the map is valid after each step.
+
+    let mut p = unsafe { self.ptr.sub(1) };
+    let mut x = unsafe { &*p }.hash;
+
+    while x == 0 {
+      p = unsafe { p.sub(1) };
+      x = unsafe { &*p }.hash;
     }
+
+    unsafe { &mut *p }.hash = 0;
+    self.map.space += 1;
+    self.ptr = p;
+
+    let x = self.rev.hash(unsafe { NonZeroU64::new_unchecked(x) });
+    let v = unsafe { (&*p).data.assume_init_read() };
+
+    Some((x, v))
+  }
+
+  #[inline(always)]
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    let k = self.map.len();
+    (k, Some(k))
+  }
+}
+
+impl<'a, T, A: Allocator> Drop for Drain<'a, T, A> {
+  fn drop(&mut self) {
+    for (_, v) in &mut *self { drop::<T>(v) }
   }
 }
 
-impl<T> Iterator for IntoValues<T> {
+impl<T, A: Allocator> Iterator for IntoValues<T, A> {
   type Item = T;
 
   #[inline(always)]
@@ -1129,7 +3267,7 @@ impl<T> Iterator for IntoValues<T> {
   }
 }
 
-impl<T> Drop for IntoValues<T> {
+impl<T, A: Allocator> Drop for IntoValues<T, A> {
   fn drop(&mut self) {
     for v in &mut *self { drop::<T>(v) }
 
@@ -1138,8 +3276,177 @@ impl<T> Drop for IntoValues<T> {
       let align = mem::align_of::<Slot<T>>();
       let layout = unsafe { Layout::from_size_align_unchecked(size, align) };
 
-      unsafe { alloc::alloc::dealloc(self.mem.0, layout) };
+      unsafe { self.alloc.deallocate(NonNull::new_unchecked(self.mem.0), layout) };
+    }
+  }
+}
+
+impl<'a, T, U, A: Allocator, B: Allocator> Iterator for Join<'a, T, U, A, B> {
+  type Item = (NonZeroU64, &'a T, &'a U);
+
+  #[inline(always)]
+  fn next(&mut self) -> Option<Self::Item> {
+    match &mut self.inner {
+      JoinInner::Left(iter, other) => {
+        loop {
+          let (k, x) = iter.next()?;
+          if let Some(y) = other.get(k) { return Some((k, x, y)); }
+        }
+      }
+      JoinInner::Right(other, iter) => {
+        loop {
+          let (k, y) = iter.next()?;
+          if let Some(x) = other.get(k) { return Some((k, x, y)); }
+        }
+      }
+    }
+  }
+
+  #[inline(always)]
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    match &self.inner {
+      JoinInner::Left(iter, _) => (0, Some(iter.len())),
+      JoinInner::Right(_, iter) => (0, Some(iter.len())),
+    }
+  }
+}
+
+impl<'a, T, U, B: Allocator> Iterator for LeftJoin<'a, T, U, B> {
+  type Item = (NonZeroU64, &'a T, Option<&'a U>);
+
+  #[inline(always)]
+  fn next(&mut self) -> Option<Self::Item> {
+    let (k, x) = self.iter.next()?;
+    Some((k, x, self.other.get(k)))
+  }
+
+  #[inline(always)]
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    self.iter.size_hint()
+  }
+}
+
+impl<'a, T, U, A: Allocator, B: Allocator> Iterator for FullJoin<'a, T, U, A, B> {
+  type Item = (NonZeroU64, Option<&'a T>, Option<&'a U>);
+
+  #[inline(always)]
+  fn next(&mut self) -> Option<Self::Item> {
+    // We first yield every key in `this`, and then every key in `other` that
+    // isn't in `this`.
+
+    if let Some((k, x)) = self.left.next() {
+      return Some((k, Some(x), self.other.get(k)));
+    }
+
+    loop {
+      let (k, y) = self.right.next()?;
+      if ! self.this.contains_key(k) { return Some((k, None, Some(y))); }
+    }
+  }
+
+  #[inline(always)]
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    let n = self.left.len();
+    let m = self.right.len();
+    (n, Some(n + m))
+  }
+}
+
+impl<'a, T, A: Allocator> Iterator for Matching<'a, T, A> {
+  type Item = (NonZeroU64, &'a T);
+
+  #[inline(always)]
+  fn next(&mut self) -> Option<Self::Item> {
+    loop {
+      let &k = self.keys.next()?;
+      if let Some(v) = self.map.get(k) { return Some((k, v)); }
+    }
+  }
+
+  #[inline(always)]
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (0, Some(self.keys.len()))
+  }
+}
+
+#[cfg(feature = "rayon")]
+const PAR_MIN_SLOTS: usize = 1 << 12;
+
+#[cfg(feature = "rayon")]
+impl<'a, T: Sync> rayon::iter::plumbing::UnindexedProducer for ParSlots<'a, T> {
+  type Item = (NonZeroU64, &'a T);
+
+  fn split(self) -> (Self, Option<Self>) {
+    let (a, b) = self.ptr;
+
+    if a == b { return (self, None); }
+
+    let n = unsafe { b.offset_from(a) } as usize;
+
+    if n < 2 * PAR_MIN_SLOTS { return (self, None); }
+
+    let m = unsafe { a.add(n / 2) };
+
+    (ParSlots { ptr: (a, m), var: PhantomData }, Some(ParSlots { ptr: (m, b), var: PhantomData }))
+  }
+
+  fn fold_with<F>(self, mut folder: F) -> F
+  where
+    F: rayon::iter::plumbing::Folder<Self::Item>
+  {
+    let (mut p, b) = self.ptr;
+
+    while p != b && ! folder.full() {
+      let x = unsafe { &*p }.hash;
+
+      if x != 0 {
+        let v = unsafe { (&*p).data.assume_init_ref() };
+        folder = folder.consume((unsafe { NonZeroU64::new_unchecked(x) }, v));
+      }
+
+      p = unsafe { p.add(1) };
     }
+
+    folder
+  }
+}
+
+#[cfg(feature = "rayon")]
+impl<'a, T: Sync> rayon::iter::ParallelIterator for ParSlots<'a, T> {
+  type Item = (NonZeroU64, &'a T);
+
+  fn drive_unindexed<C>(self, consumer: C) -> C::Result
+  where
+    C: rayon::iter::plumbing::UnindexedConsumer<Self::Item>
+  {
+    rayon::iter::plumbing::bridge_unindexed(self, consumer)
+  }
+}
+
+#[cfg(feature = "rayon")]
+impl<'a, T: Sync> rayon::iter::ParallelIterator for ParIter<'a, T> {
+  type Item = (NonZeroU64, &'a T);
+
+  fn drive_unindexed<C>(self, consumer: C) -> C::Result
+  where
+    C: rayon::iter::plumbing::UnindexedConsumer<Self::Item>
+  {
+    let m = self.rev;
+    let p = ParSlots { ptr: self.ptr, var: PhantomData };
+    rayon::iter::ParallelIterator::map(p, move |(x, v)| (m.hash(x), v)).drive_unindexed(consumer)
+  }
+}
+
+#[cfg(feature = "rayon")]
+impl<'a, T: Sync> rayon::iter::ParallelIterator for ParValues<'a, T> {
+  type Item = &'a T;
+
+  fn drive_unindexed<C>(self, consumer: C) -> C::Result
+  where
+    C: rayon::iter::plumbing::UnindexedConsumer<Self::Item>
+  {
+    let p = ParSlots { ptr: self.ptr, var: PhantomData };
+    rayon::iter::ParallelIterator::map(p, |(_, v)| v).drive_unindexed(consumer)
   }
 }
 
@@ -1148,19 +3455,91 @@ pub mod internal {
 
   use super::*;
 
-  pub fn num_slots<T>(t: &HashMapNZ64<T>) -> usize {
+  pub fn num_slots<T, A: Allocator>(t: &HashMapNZ64<T, A>) -> usize {
     t.internal_num_slots()
   }
 
-  pub fn num_bytes<T>(t: &HashMapNZ64<T>) -> usize {
+  pub fn num_bytes<T, A: Allocator>(t: &HashMapNZ64<T, A>) -> usize {
     t.internal_num_bytes()
   }
 
-  pub fn load<T>(t: &HashMapNZ64<T>) -> f64 {
+  pub fn load<T, A: Allocator>(t: &HashMapNZ64<T, A>) -> f64 {
     t.internal_load()
   }
 
-  pub fn allocation_info<T>(t: &HashMapNZ64<T>) -> Option<(NonNull<u8>, Layout)> {
+  pub fn allocation_info<T, A: Allocator>(t: &HashMapNZ64<T, A>) -> Option<(NonNull<u8>, Layout)> {
     t.internal_allocation_info()
   }
+
+  pub fn max_probe_length<T, A: Allocator>(t: &HashMapNZ64<T, A>) -> usize {
+    t.internal_max_probe_length()
+  }
+
+  /// Returns the mean distance of the items from their ideal slots, or zero if
+  /// the map is empty.
+
+  pub fn probe_length_average<T, A: Allocator>(t: &HashMapNZ64<T, A>) -> f64 {
+    t.internal_report().mean_probe
+  }
+
+  /// Returns a histogram whose `i`th entry counts the items at distance `i`
+  /// from their ideal slots. Its length is one more than the maximum probe
+  /// length.
+
+  pub fn probe_length_histogram<T, A: Allocator>(t: &HashMapNZ64<T, A>) -> Box<[usize]> {
+    t.internal_probe_length_histogram()
+  }
+
+  /// A snapshot of the table's diagnostics, computed in a single pass.
+  ///
+  /// The `capacity` is the number of items the table can hold before running
+  /// out of space, and zero if no table is allocated. The probe lengths are
+  /// the distances of the items from their ideal slots, and `grow_count` is
+  /// the number of times the map has moved to a larger table.
+
+  #[derive(Clone, Copy, Debug, PartialEq)]
+  pub struct Report {
+    pub len: usize,
+    pub capacity: usize,
+    pub num_slots: usize,
+    pub num_bytes: usize,
+    pub load: f64,
+    pub is_allocated: bool,
+    pub max_probe: usize,
+    pub mean_probe: f64,
+    pub grow_count: u64,
+  }
+
+  pub fn report<T, A: Allocator>(t: &HashMapNZ64<T, A>) -> Report {
+    t.internal_report()
+  }
+
+  pub fn spot(shift: usize, hash: u64) -> isize {
+    super::spot(shift, hash)
+  }
+
+  /// Calls `f` with the stored hash of each item and a pointer to its value.
+  ///
+  /// # Safety
+  ///
+  /// The callback may read and write the value through the pointer, but must
+  /// leave it initialized, and must not access the table in any other way.
+
+  pub unsafe fn for_each_slot<T, A: Allocator, F>(t: &mut HashMapNZ64<T, A>, mut f: F)
+  where
+    F: FnMut(NonZeroU64, *mut T)
+  {
+    let mut k = t.len();
+    let mut p = t.check as *mut Slot<T>;
+
+    while k != 0 {
+      p = unsafe { p.sub(1) };
+      let x = unsafe { &*p }.hash;
+
+      if x != 0 {
+        f(unsafe { NonZeroU64::new_unchecked(x) }, unsafe { (&mut *p).data.as_mut_ptr() });
+        k -= 1;
+      }
+    }
+  }
 }
@@ -0,0 +1,120 @@
+//! This module implements an invertible hash function on `NonZeroU64`s.
+
+use crate::prelude::*;
+
+/// An invertible hash function on `NonZeroU64`s, determined by a pair of odd
+/// multipliers and whether to reverse the bytes in between them.
+///
+/// Every mixer is a permutation of the `NonZeroU64`s, so it can also be used on
+/// its own as a reversible hash, for example to scramble sequential ids.
+///
+/// ```
+/// # use core::num::NonZeroU64;
+/// # use wordmap::mixer::Mixer;
+/// let m = Mixer::new([0x1234_5678, 0x9abc_def0]);
+/// let x = NonZeroU64::new(42).unwrap();
+/// assert_ne!(m.hash(x), x);
+/// assert_eq!(m.invert().hash(m.hash(x)), x);
+/// assert_eq!(m.invert().invert(), m);
+/// ```
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Mixer(u64, u64, bool);
+
+#[inline(always)]
+const fn invert(a: u64) -> u64 {
+  // https://arxiv.org/abs/2204.04342
+
+  let x = a.wrapping_mul(3) ^ 2;
+  let y = 1u64.wrapping_sub(a.wrapping_mul(x));
+  let x = x.wrapping_mul(y.wrapping_add(1));
+  let y = y.wrapping_mul(y);
+  let x = x.wrapping_mul(y.wrapping_add(1));
+  let y = y.wrapping_mul(y);
+  let x = x.wrapping_mul(y.wrapping_add(1));
+  let y = y.wrapping_mul(y);
+  let x = x.wrapping_mul(y.wrapping_add(1));
+  x
+}
+
+impl Mixer {
+  /// Creates a mixer from the given seed. The low bit of each word is forced
+  /// to one, so [`seed`](Self::seed) may not return exactly the given value.
+
+  #[inline(always)]
+  pub const fn new(seed: [u64; 2]) -> Self {
+    Self(seed[0] | 1, seed[1] | 1, true)
+  }
+
+  /// Creates a mixer from the given seed, like [`new`](Self::new), except that
+  /// it doesn't reverse the bytes of the value between its two multiplies.
+  ///
+  /// Without the swap, the two multiplies collapse into a single multiply by
+  /// the product of the seed words, so this gives no more mixing than one
+  /// multiply. In particular, the seed of a mixer from
+  /// [`new_seeded`](Self::new_seeded) has words that are inverses of each
+  /// other, and yields the identity hash.
+  ///
+  /// This is only useful for keys that are themselves already byte-reversed
+  /// hashes. Otherwise, keys that differ only in their high bits hash to
+  /// values that also differ only in their high bits.
+
+  #[inline(always)]
+  pub const fn new_no_swap(seed: [u64; 2]) -> Self {
+    Self(seed[0] | 1, seed[1] | 1, false)
+  }
+
+  /// Creates a random mixer using the given random number generator.
+
+  #[inline(always)]
+  pub fn new_seeded(rng: &mut Rng) -> Self {
+    let a = rng.u64() | 1;
+    let b = invert(a);
+    Self(a, b, true)
+  }
+
+  /// Returns the seed from which an identical mixer can be recreated with
+  /// [`new`](Self::new), or with [`new_no_swap`](Self::new_no_swap) if the
+  /// mixer doesn't [`swap_bytes`](Self::swap_bytes).
+
+  #[inline(always)]
+  pub const fn seed(self) -> [u64; 2] {
+    [self.0, self.1]
+  }
+
+  /// Returns whether the mixer reverses the bytes of the value between its
+  /// two multiplies.
+
+  #[inline(always)]
+  pub const fn swap_bytes(self) -> bool {
+    self.2
+  }
+
+  /// Hashes the given value.
+
+  #[inline(always)]
+  pub const fn hash(self, x: NonZeroU64) -> NonZeroU64 {
+    let Self(a, b, c) = self;
+    let x = x.get();
+    let x = x.wrapping_mul(a);
+    let x = if c { x.swap_bytes() } else { x };
+    let x = x.wrapping_mul(b);
+    unsafe { NonZeroU64::new_unchecked(x) }
+  }
+
+  /// Returns the inverse mixer, whose hash function undoes this one's.
+
+  #[inline(always)]
+  pub const fn invert(self) -> Self {
+    let Self(a, b, c) = self;
+    Self(invert(b), invert(a), c)
+  }
+
+  /// Recovers the value whose hash is `x`. This is the same as
+  /// `self.invert().hash(x)`.
+
+  #[inline(always)]
+  pub const fn unhash(self, x: NonZeroU64) -> NonZeroU64 {
+    self.invert().hash(x)
+  }
+}
@@ -1,7 +1,10 @@
 pub(crate) extern crate alloc;
 
+pub(crate) use alloc::boxed::Box;
+pub(crate) use alloc::vec::Vec;
+pub(crate) use alloc::vec;
+
 pub(crate) use core::alloc::Layout;
-pub(crate) use core::cell::Cell;
 pub(crate) use core::cmp::max;
 pub(crate) use core::fmt;
 pub(crate) use core::iter::FusedIterator;
@@ -15,6 +18,9 @@ pub(crate) use core::ops::Index;
 pub(crate) use core::ops::IndexMut;
 pub(crate) use core::ptr::NonNull;
 pub(crate) use core::ptr;
+pub(crate) use crate::allocator::Allocator;
+pub(crate) use crate::allocator::Global;
+pub(crate) use crate::mixer::Mixer;
 pub(crate) use crate::ptr::Ptr;
 pub(crate) use crate::rng::Rng;
 pub(crate) use crate::rng;
@@ -26,17 +26,34 @@ impl Ptr {
 
   #[inline(always)]
   pub const fn invalid(addr: usize) -> Self {
-    Self(unsafe { core::mem::transmute::<usize, *const u8>(addr) })
+    Self(core::ptr::without_provenance(addr))
   }
 
   #[inline(always)]
   pub fn addr(self) -> usize {
     // NB: This must not be a `const` function.
     //
-    // In particular, transmuting a pointer into an integer is undefined
-    // behavior in a const context.
+    // In particular, the address of a pointer isn't known in a const context.
 
-    unsafe { core::mem::transmute::<*const u8, usize>(self.0) }
+    self.0.addr()
+  }
+
+  /// Returns a pointer with the given address and the provenance of `self`.
+
+  #[inline(always)]
+  pub fn with_addr(self, addr: usize) -> Self {
+    Self(self.0.with_addr(addr))
+  }
+
+  /// Returns a pointer whose address is `f` applied to the address of `self`,
+  /// with the provenance of `self`.
+
+  #[inline(always)]
+  pub fn map_addr<F>(self, f: F) -> Self
+  where
+    F: FnOnce(usize) -> usize
+  {
+    Self(self.0.map_addr(f))
   }
 
   #[inline(always)]
@@ -84,11 +101,53 @@ impl Ptr {
     self.addr().wrapping_sub(offset.addr()) as isize
   }
 
+  /// Returns the distance from `origin` to `self` in units of `T`. The
+  /// distance in bytes must be a multiple of the size of `T`, which is checked
+  /// in debug builds.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `T` is zero-sized.
+
+  #[inline(always)]
+  pub fn offset_from_count<T>(self, origin: Self) -> isize {
+    let n = core::mem::size_of::<T>() as isize;
+    assert!(n != 0, "Ptr::offset_from_count: T must not be zero-sized");
+    let d = self.diff(origin);
+    debug_assert!(d % n == 0, "Ptr::offset_from_count: distance isn't a multiple of the size");
+    d / n
+  }
+
   #[inline(always)]
   pub fn mask(self, mask: usize) -> Self {
     Self(self.0.wrapping_sub(self.addr() & ! mask))
   }
 
+  /// Returns the number of bytes to add to `self` to align it to `align`,
+  /// which must be a power of two. As with `<*const u8>::align_offset`, this
+  /// may be `usize::MAX` if that isn't possible.
+
+  #[inline(always)]
+  pub fn align_offset(self, align: usize) -> usize {
+    self.0.align_offset(align)
+  }
+
+  /// Returns whether the address is a multiple of `align`, which must be a
+  /// power of two.
+
+  #[inline(always)]
+  pub fn is_aligned_to(self, align: usize) -> bool {
+    debug_assert!(align.is_power_of_two());
+    self.addr() & (align - 1) == 0
+  }
+
+  /// Returns whether the address is suitably aligned for a `T`.
+
+  #[inline(always)]
+  pub fn is_aligned<T>(self) -> bool {
+    self.is_aligned_to(core::mem::align_of::<T>())
+  }
+
   #[inline(always)]
   pub const fn gep<T>(self, index: isize) -> Self {
     self.add(offset_of_element_at_index::<T>(index))
@@ -130,6 +189,32 @@ impl Ptr {
     unsafe { x.write_volatile(value) }
   }
 
+  /// Reads the element at the given index of an array of `T`s starting at
+  /// `self`. The index may be negative.
+  ///
+  /// # Safety
+  ///
+  /// The offset pointer `self.gep::<T>(index)` must be in bounds of the same
+  /// allocation as `self`, aligned for `T`, and point to an initialized `T`.
+
+  #[inline(always)]
+  pub unsafe fn read_at<T>(self, index: isize) -> T {
+    unsafe { self.gep::<T>(index).read() }
+  }
+
+  /// Writes the element at the given index of an array of `T`s starting at
+  /// `self`, without dropping the old value. The index may be negative.
+  ///
+  /// # Safety
+  ///
+  /// The offset pointer `self.gep::<T>(index)` must be in bounds of the same
+  /// allocation as `self`, aligned for `T`, and valid for writes of a `T`.
+
+  #[inline(always)]
+  pub unsafe fn write_at<T>(self, index: isize, value: T) {
+    unsafe { self.gep::<T>(index).write(value) }
+  }
+
   #[inline(always)]
   pub unsafe fn replace<T>(self, value: T) -> T {
     let x = self.as_mut_ptr::<T>();
@@ -322,3 +407,9 @@ impl core::fmt::Debug for Ptr {
     write!(out, "0x{:01$x}", self.addr(), (usize::BITS / 4) as usize)
   }
 }
+
+impl core::fmt::Display for Ptr {
+  fn fmt(&self, out: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    core::fmt::Debug::fmt(self, out)
+  }
+}
@@ -3,6 +3,8 @@ use crate::prelude::*;
 #[derive(Clone)]
 pub struct Rng(NonZeroU128);
 
+const M: u128 = 0x487e_d511_0b46_11a6_2633_145c_06e0_e689;
+
 #[inline(always)]
 fn mul(x: u64, y: u64) -> u128 {
   (x as u128) * (y as u128)
@@ -23,6 +25,16 @@ fn concat(x: u64, y: u64) -> u128 {
   (x as u128) ^ ((y as u128) << 64)
 }
 
+/// Returns the high 64 bits of the full 128-bit product of `x` and `y`.
+///
+/// This is handy for fixed-point arithmetic and for Lemire's multiply-shift
+/// reduction of a random `u64` to a bounded range.
+
+#[inline(always)]
+pub const fn umulh(x: u64, y: u64) -> u64 {
+  (((x as u128) * (y as u128)) >> 64) as u64
+}
+
 impl Rng {
   #[inline(always)]
   pub const fn new(state: NonZeroU128) -> Self {
@@ -37,7 +49,6 @@ impl Rng {
   }
 
   pub fn from_u64(n: u64) -> Self {
-    const M: u128 = 0x487e_d511_0b46_11a6_2633_145c_06e0_e689;
     let s = concat(n, 1);
     let s = s.wrapping_mul(M);
     let s = s.swap_bytes();
@@ -48,6 +59,26 @@ impl Rng {
     Self(s)
   }
 
+  /// Derives a generator from a base seed and a label, such as the name of an
+  /// experiment. The same base and label always produce the same stream, and
+  /// different labels produce unrelated streams.
+
+  pub fn for_label(base: NonZeroU128, label: &[u8]) -> Self {
+    let mut s = base.get() ^ label.len() as u128;
+    for chunk in label.chunks(16) {
+      let mut x = [0; 16];
+      x[.. chunk.len()].copy_from_slice(chunk);
+      s ^= u128::from_le_bytes(x);
+      s = s.wrapping_mul(M).swap_bytes();
+    }
+    let s = s.wrapping_mul(M);
+    let s = s.swap_bytes();
+    let s = s.wrapping_mul(M);
+    let s = s ^ (s == 0) as u128;
+    let s = unsafe { NonZeroU128::new_unchecked(s) };
+    Self(s)
+  }
+
   #[inline(never)]
   pub fn from_entropy() -> Self {
     let mut seed = [0; 16];
@@ -75,11 +106,24 @@ impl Rng {
     x
   }
 
+  /// Derives a child generator from the next outputs of this one, advancing
+  /// it. Splitting a generator seeded the same way always produces the same
+  /// children, so each worker in a parallel job can get its own reproducible
+  /// stream from a single seed.
+  ///
+  /// The child's state is a scrambled function of the parent's outputs. The
+  /// streams are statistically unrelated in practice, but nothing guarantees
+  /// that they don't overlap, and the generator isn't cryptographically
+  /// secure.
+
   #[inline(always)]
   pub fn split(&mut self) -> Self {
     let a = self.u64();
     let b = self.u64();
     let s = concat(a, b);
+    let s = s.wrapping_mul(M);
+    let s = s.swap_bytes();
+    let s = s.wrapping_mul(M);
     let s = s ^ (s == 0) as u128;
     let s = unsafe { NonZeroU128::new_unchecked(s) };
     Self(s)
@@ -95,19 +139,196 @@ impl Rng {
     self.i64() >= 0
   }
 
+  /// Returns the high 32 bits of a call to [`u64`](Self::u64).
+
+  #[inline(always)]
+  pub fn u32(&mut self) -> u32 {
+    (self.u64() >> 32) as u32
+  }
+
+  /// Returns two calls to [`u64`](Self::u64) combined, the first forming the
+  /// low half.
+
+  #[inline(always)]
+  pub fn u128(&mut self) -> u128 {
+    let a = self.u64();
+    let b = self.u64();
+    concat(a, b)
+  }
+
+  /// Returns a uniformly distributed nonzero integer, which is always a valid
+  /// map key. This draws again in the vanishingly rare case of a zero.
+
+  #[inline(always)]
+  pub fn nonzero_u64(&mut self) -> NonZeroU64 {
+    loop {
+      if let Some(x) = NonZeroU64::new(self.u64()) { return x; }
+    }
+  }
+
+  /// Returns a uniformly distributed float in `[0, 1)`, taking the top 53
+  /// bits of a call to [`u64`](Self::u64) as the fraction, so the result is
+  /// never exactly `1.0`.
+
+  #[inline(always)]
+  pub fn f64(&mut self) -> f64 {
+    (self.u64() >> 11) as f64 * (1. / (1u64 << 53) as f64)
+  }
+
+  /// Returns a uniformly distributed float in `[lo, hi)`. The range must be
+  /// nonempty, that is, `lo < hi`, which is checked in debug builds.
+  ///
+  /// Scaling can round up to `hi`, in which case this returns the largest
+  /// float below it instead.
+
+  #[inline(always)]
+  pub fn f64_range(&mut self, lo: f64, hi: f64) -> f64 {
+    debug_assert!(lo < hi, "empty range");
+
+    let x = lo + (hi - lo) * self.f64();
+    if x < hi { x } else { hi.next_down() }
+  }
+
   #[inline(always)]
   pub fn bounded_u32(&mut self, max: u32) -> u32 {
-    hi(mul(self.u64(), (max as u64) + 1)) as u32
+    umulh(self.u64(), (max as u64) + 1) as u32
   }
 
   #[inline(always)]
   pub fn inclusive_range_u32(&mut self, a: u32, b: u32) -> u32 {
     a.wrapping_add(self.bounded_u32(b.wrapping_sub(a)))
   }
+
+  /// Shuffles the slice in place, with each permutation equally likely.
+
+  pub fn shuffle<T>(&mut self, slice: &mut [T]) {
+    // This is the Fisher-Yates shuffle.
+
+    for i in (1 .. slice.len()).rev() {
+      let j = self.u64_below(i as u64 + 1) as usize;
+      slice.swap(i, j);
+    }
+  }
+
+  /// Returns a uniformly chosen element of the slice, or `None` if it is
+  /// empty.
+
+  #[inline(always)]
+  pub fn choose<'a, T>(&mut self, slice: &'a [T]) -> Option<&'a T> {
+    if slice.is_empty() { return None; }
+    Some(&slice[self.u64_below(slice.len() as u64) as usize])
+  }
+
+  /// Fills the buffer with random bytes, taking each group of eight from the
+  /// little-endian bytes of a call to [`u64`](Self::u64).
+
+  #[inline(always)]
+  pub fn fill_bytes(&mut self, dst: &mut [u8]) {
+    let mut chunks = dst.chunks_exact_mut(8);
+
+    for chunk in &mut chunks {
+      chunk.copy_from_slice(&self.u64().to_le_bytes());
+    }
+
+    let rest = chunks.into_remainder();
+
+    if ! rest.is_empty() {
+      let n = rest.len();
+      rest.copy_from_slice(&self.u64().to_le_bytes()[.. n]);
+    }
+  }
+
+  /// Returns an array of random bytes, as filled by
+  /// [`fill_bytes`](Self::fill_bytes).
+
+  #[inline(always)]
+  pub fn array_u8<const N: usize>(&mut self) -> [u8; N] {
+    let mut a = [0; N];
+    self.fill_bytes(&mut a);
+    a
+  }
+
+  /// Returns a uniformly distributed integer in `0 .. bound`, without the bias
+  /// of reducing modulo `bound`.
+  ///
+  /// This is Lemire's multiply-shift method, which rejects the samples whose
+  /// low half falls in the short biased region.
+  ///
+  /// # Panics
+  ///
+  /// Panics if `bound` is zero.
+
+  #[inline(always)]
+  pub fn u64_below(&mut self, bound: u64) -> u64 {
+    assert!(bound != 0, "Rng::u64_below: bound must be nonzero");
+
+    let mut x = mul(self.u64(), bound);
+
+    if lo(x) < bound {
+      let t = bound.wrapping_neg() % bound;
+
+      while lo(x) < t {
+        x = mul(self.u64(), bound);
+      }
+    }
+
+    hi(x)
+  }
+}
+
+#[cfg(feature = "rand_core")]
+impl rand_core::RngCore for Rng {
+  #[inline(always)]
+  fn next_u32(&mut self) -> u32 {
+    self.u32()
+  }
+
+  #[inline(always)]
+  fn next_u64(&mut self) -> u64 {
+    self.u64()
+  }
+
+  #[inline(always)]
+  fn fill_bytes(&mut self, dst: &mut [u8]) {
+    Rng::fill_bytes(self, dst)
+  }
+
+  #[inline(always)]
+  fn try_fill_bytes(&mut self, dst: &mut [u8]) -> Result<(), rand_core::Error> {
+    Rng::fill_bytes(self, dst);
+    Ok(())
+  }
+}
+
+#[cfg(feature = "rand_core")]
+impl rand_core::SeedableRng for Rng {
+  type Seed = [u8; 16];
+
+  fn from_seed(seed: [u8; 16]) -> Self {
+    Rng::from_seed(seed)
+  }
+}
+
+/// Calls `f` with the generator used to seed new maps. With the `std` feature,
+/// this is the thread-local generator. Otherwise, it is a fresh generator
+/// seeded from the operating system.
+
+#[inline(always)]
+pub(crate) fn with_default<F, T>(f: F) -> T
+where
+  F: FnOnce(&mut Rng) -> T
+{
+  #[cfg(feature = "std")]
+  { thread_local::with(f) }
+
+  #[cfg(not(feature = "std"))]
+  { f(&mut Rng::from_entropy()) }
 }
 
+#[cfg(feature = "std")]
 pub mod thread_local {
   use super::*;
+  use core::cell::Cell;
 
   std::thread_local! {
     static RNG: Cell<Option<NonZeroU128>> = const { Cell::new(None) };
@@ -0,0 +1,110 @@
+//! This module implements `Serialize` and `Deserialize` for the map and set.
+//!
+//! Maps serialize as maps and sets as sequences, in increasing order of keys
+//! so that the output is deterministic. Deserializing creates a fresh mixer.
+
+use crate::prelude::*;
+use crate::map::HashMapNZ64;
+use crate::set::HashSetNZ64;
+use serde::de::Deserialize;
+use serde::de::Deserializer;
+use serde::de::MapAccess;
+use serde::de::SeqAccess;
+use serde::de::Visitor;
+use serde::ser::Serialize;
+use serde::ser::SerializeMap;
+use serde::ser::SerializeSeq;
+use serde::ser::Serializer;
+
+// NB: We don't trust the length hint from the input, so we cap the amount we
+// reserve up front.
+
+const MAX_RESERVE: usize = 1 << 16;
+
+impl<T: Serialize, A: Allocator> Serialize for HashMapNZ64<T, A> {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut a = self.iter().collect::<Vec<_>>();
+
+    a.sort_unstable_by_key(|x| x.0);
+
+    let mut m = serializer.serialize_map(Some(a.len()))?;
+
+    for (key, value) in a.iter() {
+      m.serialize_entry(key, value)?;
+    }
+
+    m.end()
+  }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for HashMapNZ64<T> {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    struct MapVisitor<T>(PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>> Visitor<'de> for MapVisitor<T> {
+      type Value = HashMapNZ64<T>;
+
+      fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a map keyed by nonzero u64s")
+      }
+
+      fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+        let mut t = HashMapNZ64::new();
+
+        t.reserve(access.size_hint().unwrap_or(0).min(MAX_RESERVE));
+
+        while let Some((key, value)) = access.next_entry()? {
+          let _: _ = t.insert(key, value);
+        }
+
+        Ok(t)
+      }
+    }
+
+    deserializer.deserialize_map(MapVisitor(PhantomData))
+  }
+}
+
+impl Serialize for HashSetNZ64 {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut a = self.iter().collect::<Vec<_>>();
+
+    a.sort_unstable();
+
+    let mut s = serializer.serialize_seq(Some(a.len()))?;
+
+    for key in a.iter() {
+      s.serialize_element(key)?;
+    }
+
+    s.end()
+  }
+}
+
+impl<'de> Deserialize<'de> for HashSetNZ64 {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    struct SetVisitor;
+
+    impl<'de> Visitor<'de> for SetVisitor {
+      type Value = HashSetNZ64;
+
+      fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a sequence of nonzero u64s")
+      }
+
+      fn visit_seq<A: SeqAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+        let mut s = HashSetNZ64::new();
+
+        s.reserve(access.size_hint().unwrap_or(0).min(MAX_RESERVE));
+
+        while let Some(key) = access.next_element()? {
+          let _: _ = s.insert(key);
+        }
+
+        Ok(s)
+      }
+    }
+
+    deserializer.deserialize_seq(SetVisitor)
+  }
+}
@@ -1,11 +1,21 @@
 use crate::prelude::*;
 use crate::map::HashMapNZ64;
+use core::ops::BitAnd;
+use core::ops::BitOr;
+use core::ops::BitXor;
+use core::ops::Sub;
 
+// NB: The derived `PartialEq` compares membership, regardless of mixers,
+// since that is how the map compares its contents.
+
+#[derive(Clone, Eq, PartialEq)]
 pub struct HashSetNZ64(HashMapNZ64<()>);
 
 #[derive(Clone)]
 pub struct Iter<'a>(crate::map::Keys<'a, ()>);
 
+pub struct IntoIter(crate::map::IntoIter<()>);
+
 impl<'a> FusedIterator for Iter<'a> {}
 impl<'a> ExactSizeIterator for Iter<'a> {}
 
@@ -18,6 +28,14 @@ impl HashSetNZ64 {
     Self(HashMapNZ64::new_seeded(rng))
   }
 
+  pub const fn new_with_mixer(mixer: Mixer) -> Self {
+    Self(HashMapNZ64::new_with_mixer(mixer))
+  }
+
+  pub fn mixer(&self) -> Mixer {
+    self.0.mixer()
+  }
+
   pub fn len(&self) -> usize {
     self.0.len()
   }
@@ -26,14 +44,39 @@ impl HashSetNZ64 {
     self.0.is_empty()
   }
 
+  pub fn reserve(&mut self, additional: usize) {
+    self.0.reserve(additional)
+  }
+
   pub fn contains(&self, key: NonZeroU64) -> bool {
     self.0.contains_key(key)
   }
 
+  pub fn contains_all(&self, keys: &[NonZeroU64]) -> bool {
+    if self.is_empty() { return keys.is_empty(); }
+
+    keys.iter().all(|&key| self.contains(key))
+  }
+
+  pub fn retain_contained(&self, keys: &mut Vec<NonZeroU64>) {
+    if self.is_empty() { keys.clear(); return; }
+
+    keys.retain(|&key| self.contains(key))
+  }
+
   pub fn insert(&mut self, key: NonZeroU64) -> bool {
     self.0.insert(key, ()).is_some()
   }
 
+  pub fn insert_new(&mut self, keys: &[NonZeroU64], out: &mut Vec<NonZeroU64>) {
+    self.reserve(keys.len());
+    out.reserve(keys.len());
+
+    for &key in keys.iter() {
+      if ! self.insert(key) { out.push(key); }
+    }
+  }
+
   pub fn remove(&mut self, key: NonZeroU64) -> bool {
     self.0.remove(key).is_some()
   }
@@ -49,6 +92,175 @@ impl HashSetNZ64 {
   pub fn iter(&self) -> Iter<'_> {
     Iter(self.0.keys())
   }
+
+  pub fn is_subset(&self, other: &Self) -> bool {
+    self.len() <= other.len() && self.iter().all(|key| other.contains(key))
+  }
+
+  pub fn is_superset(&self, other: &Self) -> bool {
+    other.is_subset(self)
+  }
+
+  pub fn is_disjoint(&self, other: &Self) -> bool {
+    let (a, b) = if self.len() <= other.len() { (self, other) } else { (other, self) };
+    a.iter().all(|key| ! b.contains(key))
+  }
+
+  pub fn union(&self, other: &Self) -> Self {
+    let (a, b) = if self.len() >= other.len() { (self, other) } else { (other, self) };
+    let mut s = Self::new();
+
+    s.reserve(a.len());
+
+    for key in a.iter().chain(b.iter()) {
+      let _: _ = s.insert(key);
+    }
+
+    s
+  }
+
+  pub fn intersection(&self, other: &Self) -> Self {
+    let (a, b) = if self.len() <= other.len() { (self, other) } else { (other, self) };
+    let mut s = Self::new();
+
+    for key in a.iter() {
+      if b.contains(key) { let _: _ = s.insert(key); }
+    }
+
+    s
+  }
+
+  pub fn difference(&self, other: &Self) -> Self {
+    let mut s = Self::new();
+
+    s.reserve(self.len().saturating_sub(other.len()));
+
+    for key in self.iter() {
+      if ! other.contains(key) { let _: _ = s.insert(key); }
+    }
+
+    s
+  }
+
+  pub fn symmetric_difference(&self, other: &Self) -> Self {
+    let mut s = Self::new();
+
+    s.reserve(self.len().abs_diff(other.len()));
+
+    for key in self.iter() {
+      if ! other.contains(key) { let _: _ = s.insert(key); }
+    }
+
+    for key in other.iter() {
+      if ! self.contains(key) { let _: _ = s.insert(key); }
+    }
+
+    s
+  }
+}
+
+impl Default for HashSetNZ64 {
+  #[inline(always)]
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// Creates a set from an array of keys.
+///
+/// ```
+/// # use core::num::NonZeroU64;
+/// # use wordmap::set::HashSetNZ64;
+/// let k = |i| NonZeroU64::new(i).unwrap();
+/// let s = HashSetNZ64::from([k(1), k(2), k(1)]);
+/// assert_eq!(s.len(), 2);
+/// assert!(s.contains(k(2)));
+/// ```
+
+impl<const N: usize> From<[NonZeroU64; N]> for HashSetNZ64 {
+  fn from(keys: [NonZeroU64; N]) -> Self {
+    let mut s = Self::new();
+
+    for key in keys {
+      let _: _ = s.insert(key);
+    }
+
+    s
+  }
+}
+
+impl FromIterator<NonZeroU64> for HashSetNZ64 {
+  fn from_iter<I: IntoIterator<Item = NonZeroU64>>(iter: I) -> Self {
+    let mut s = Self::new();
+    s.extend(iter);
+    s
+  }
+}
+
+impl Extend<NonZeroU64> for HashSetNZ64 {
+  fn extend<I: IntoIterator<Item = NonZeroU64>>(&mut self, iter: I) {
+    self.0.extend(iter.into_iter().map(|key| (key, ())))
+  }
+}
+
+impl IntoIterator for HashSetNZ64 {
+  type Item = NonZeroU64;
+  type IntoIter = IntoIter;
+
+  fn into_iter(self) -> IntoIter {
+    IntoIter(self.0.into_iter())
+  }
+}
+
+/// Returns the union of two sets.
+///
+/// ```
+/// # use core::num::NonZeroU64;
+/// # use wordmap::set::HashSetNZ64;
+/// let k = |i| NonZeroU64::new(i).unwrap();
+/// let a = HashSetNZ64::from([k(1), k(2)]);
+/// let b = HashSetNZ64::from([k(2), k(3)]);
+/// let c = &a | &b;
+/// assert_eq!(c.len(), 3);
+/// assert!(c.contains(k(1)) && c.contains(k(3)));
+/// ```
+
+impl BitOr<&HashSetNZ64> for &HashSetNZ64 {
+  type Output = HashSetNZ64;
+
+  fn bitor(self, other: &HashSetNZ64) -> HashSetNZ64 {
+    self.union(other)
+  }
+}
+
+/// Returns the intersection of two sets.
+
+impl BitAnd<&HashSetNZ64> for &HashSetNZ64 {
+  type Output = HashSetNZ64;
+
+  fn bitand(self, other: &HashSetNZ64) -> HashSetNZ64 {
+    self.intersection(other)
+  }
+}
+
+/// Returns the keys in the first set but not the second.
+
+impl Sub<&HashSetNZ64> for &HashSetNZ64 {
+  type Output = HashSetNZ64;
+
+  fn sub(self, other: &HashSetNZ64) -> HashSetNZ64 {
+    self.difference(other)
+  }
+}
+
+/// Returns the keys in exactly one of the two sets.
+
+impl BitXor<&HashSetNZ64> for &HashSetNZ64 {
+  type Output = HashSetNZ64;
+
+  fn bitxor(self, other: &HashSetNZ64) -> HashSetNZ64 {
+    self.symmetric_difference(other)
+  }
 }
 
 impl fmt::Debug for HashSetNZ64 {
@@ -78,3 +290,15 @@ impl<'a> Iterator for Iter<'a> {
     self.0.size_hint()
   }
 }
+
+impl Iterator for IntoIter {
+  type Item = NonZeroU64;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    Some(self.0.next()?.0)
+  }
+
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    self.0.size_hint()
+  }
+}
@@ -0,0 +1,635 @@
+//! This module implements a hash map keyed by `NonZeroU64`s that stores its
+//! hashes and values in separate parallel arrays.
+
+use crate::prelude::*;
+
+/// A hash map keyed by `NonZeroU64`s that stores its hashes and values in
+/// separate parallel arrays.
+///
+/// The algorithm and the layout of the hash array are the same as for
+/// [`HashMapNZ64`](crate::map::HashMapNZ64), but probing only touches the
+/// compact array of hashes. When values are large, a probe sequence then spans
+/// far fewer cache lines, and the value array is touched exactly once, and
+/// only on a hit.
+
+pub struct SoaMapNZ64<T> {
+  mixer: Mixer,
+  table: *const u64,
+  slots: *const T, // covariant in `T`
+  shift: usize,
+  space: isize,
+  check: *const u64,
+}
+
+unsafe impl<T: Send> Send for SoaMapNZ64<T> {}
+
+unsafe impl<T: Sync> Sync for SoaMapNZ64<T> {}
+
+// NB: The value for the hash at `table.offset(i)` is at `slots.offset(i)`. A
+// value is initialized exactly when its hash is nonzero.
+
+const INITIAL_S: usize = 60;                        // shift
+const INITIAL_C: isize = 1 << (64 - INITIAL_S - 1); // capacity
+const INITIAL_D: usize = 1 << (64 - INITIAL_S);     // regular slots
+const INITIAL_E: usize = 8;                         // extra slots
+const INITIAL_N: usize = INITIAL_D + INITIAL_E;     // table length, total
+const INITIAL_R: isize = INITIAL_C;                 // remaining capacity
+
+#[inline(always)]
+fn spot(shift: usize, h: u64) -> isize {
+  debug_assert!((1 ..= 63).contains(&shift));
+
+  h.wrapping_shr(shift as u32) as isize
+}
+
+#[inline(always)]
+fn layouts<T>(n: usize) -> (Layout, Layout) {
+  let hashes = Layout::array::<u64>(n).unwrap();
+  let values = Layout::array::<T>(n).unwrap();
+  (hashes, values)
+}
+
+#[inline(always)]
+unsafe fn alloc_values<T>(layout: Layout) -> *mut T {
+  if layout.size() == 0 { return NonNull::dangling().as_ptr(); }
+
+  let a = unsafe { alloc::alloc::alloc(layout) } as *mut T;
+  if a.is_null() { match alloc::alloc::handle_alloc_error(layout) {} }
+  a
+}
+
+#[inline(always)]
+unsafe fn dealloc_values<T>(a: *mut T, layout: Layout) {
+  if layout.size() == 0 { return; }
+
+  unsafe { alloc::alloc::dealloc(a as *mut u8, layout) };
+}
+
+impl<T> SoaMapNZ64<T> {
+  /// Creates an empty map, seeding the hash function from a thread-local
+  /// random number generator.
+
+  #[inline(always)]
+  pub fn new() -> Self {
+    rng::with_default(|rng| Self::new_seeded(rng))
+  }
+
+  /// Creates an empty map, seeding the hash function from the given random
+  /// number generator.
+
+  #[inline(always)]
+  pub fn new_seeded(rng: &mut Rng) -> Self {
+    Self::new_with_mixer(Mixer::new_seeded(rng))
+  }
+
+  /// Creates an empty map that hashes keys with the given mixer.
+
+  #[inline(always)]
+  pub const fn new_with_mixer(mixer: Mixer) -> Self {
+    Self {
+      mixer,
+      table: ptr::null(),
+      slots: ptr::null(),
+      shift: INITIAL_S,
+      space: INITIAL_R,
+      check: ptr::null(),
+    }
+  }
+
+  /// Returns the mixer used to hash keys.
+
+  #[inline(always)]
+  pub fn mixer(&self) -> Mixer {
+    self.mixer
+  }
+
+  /// Returns the number of items.
+
+  #[inline(always)]
+  pub fn len(&self) -> usize {
+    let s = self.shift;
+    let r = self.space;
+    let c = 1 << (64 - s - 1);
+    (c - r) as usize
+  }
+
+  /// Returns whether the map contains zero items.
+
+  #[inline(always)]
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// Returns whether the map contains the given key.
+
+  #[inline(always)]
+  pub fn contains_key(&self, key: NonZeroU64) -> bool {
+    let t = self.table;
+
+    if t.is_null() { return false; }
+
+    let m = self.mixer;
+    let s = self.shift;
+    let h = m.hash(key).get();
+
+    let mut p = unsafe { t.offset(- spot(s, h)) };
+    let mut x = unsafe { *p };
+
+    while x > h {
+      p = unsafe { p.add(1) };
+      x = unsafe { *p };
+    }
+
+    x == h
+  }
+
+  /// Returns a reference to the value associated with the given key, if
+  /// present.
+
+  #[inline(always)]
+  pub fn get(&self, key: NonZeroU64) -> Option<&T> {
+    let t = self.table;
+
+    if t.is_null() { return None; }
+
+    let m = self.mixer;
+    let s = self.shift;
+    let h = m.hash(key).get();
+
+    let mut p = unsafe { t.offset(- spot(s, h)) };
+    let mut x = unsafe { *p };
+
+    while x > h {
+      p = unsafe { p.add(1) };
+      x = unsafe { *p };
+    }
+
+    if x != h { return None; }
+
+    let i = unsafe { p.offset_from(t) };
+
+    Some(unsafe { &*self.slots.offset(i) })
+  }
+
+  /// Returns a mutable reference to the value associated with the given key,
+  /// if present.
+
+  #[inline(always)]
+  pub fn get_mut(&mut self, key: NonZeroU64) -> Option<&mut T> {
+    let t = self.table;
+
+    if t.is_null() { return None; }
+
+    let m = self.mixer;
+    let s = self.shift;
+    let h = m.hash(key).get();
+
+    let mut p = unsafe { t.offset(- spot(s, h)) };
+    let mut x = unsafe { *p };
+
+    while x > h {
+      p = unsafe { p.add(1) };
+      x = unsafe { *p };
+    }
+
+    if x != h { return None; }
+
+    let i = unsafe { p.offset_from(t) };
+
+    Some(unsafe { &mut *(self.slots as *mut T).offset(i) })
+  }
+
+  #[inline(never)]
+  #[cold]
+  unsafe fn internal_init_table_and_insert(&mut self, h: u64, value: T) {
+    let (hashes_layout, values_layout) = layouts::<T>(INITIAL_N);
+
+    let a = unsafe { alloc::alloc::alloc_zeroed(hashes_layout) } as *mut u64;
+    if a.is_null() { match alloc::alloc::handle_alloc_error(hashes_layout) {} }
+
+    let w = unsafe { alloc_values::<T>(values_layout) };
+
+    let t = unsafe { a.add(INITIAL_D - 1) };
+    let y = unsafe { w.add(INITIAL_D - 1) };
+    let b = unsafe { a.add(INITIAL_N - 1) };
+
+    let i = - spot(INITIAL_S, h);
+
+    unsafe { *t.offset(i) = h };
+    unsafe { y.offset(i).write(value) };
+
+    // We only modify `self` after we know that allocation has succeeded.
+
+    self.table = t;
+    self.slots = y;
+    self.shift = INITIAL_S;
+    self.space = INITIAL_R - 1;
+    self.check = b;
+  }
+
+  #[inline(never)]
+  #[cold]
+  unsafe fn internal_grow_table(&mut self) {
+    let old_t = self.table as *mut u64;
+    let old_y = self.slots as *mut T;
+    let old_s = self.shift;
+    let old_r = self.space;
+    let old_b = self.check as *mut u64;
+
+    let old_b_hash = unsafe { *old_b };
+    let is_overfull = old_r < 0;
+    let is_overflow = old_b_hash != 0;
+
+    let old_c = 1 << (64 - old_s - 1);
+    let old_d = 1 << (64 - old_s);
+    let old_e = unsafe { old_b.offset_from(old_t) } as usize;
+    let old_n = old_d + old_e;
+    let old_a = unsafe { old_t.sub(old_d - 1) };
+    let old_w = unsafe { old_y.sub(old_d - 1) };
+    let old_u = 64 - old_s;
+    let old_v = old_e.trailing_zeros() as usize;
+
+    let new_u = old_u + is_overfull as usize;
+    let new_v = old_v + is_overflow as usize;
+
+    assert!(new_u < usize::BITS as usize);
+    assert!(new_v <= usize::BITS as usize - 2);
+
+    let new_s = 64 - new_u;
+    let new_c = 1 << (64 - new_s - 1);
+    let new_d = 1 << (64 - new_s);
+    let new_e = 1 << new_v;
+    let new_n = new_d + new_e;
+    let new_r = old_r + (new_c - old_c);
+
+    let (old_hashes_layout, old_values_layout) = layouts::<T>(old_n);
+    let (new_hashes_layout, new_values_layout) = layouts::<T>(new_n);
+
+    // WARNING!
+    //
+    // As for `HashMapNZ64`, we must leave the map in a valid state even if
+    // allocation panics, so we temporarily remove the item in the final slot.
+
+    if is_overflow {
+      unsafe { *old_b = 0 };
+      self.space = old_r + 1;
+    }
+
+    let new_a = unsafe { alloc::alloc::alloc_zeroed(new_hashes_layout) } as *mut u64;
+    if new_a.is_null() { match alloc::alloc::handle_alloc_error(new_hashes_layout) {} }
+
+    let new_w = unsafe { alloc_values::<T>(new_values_layout) };
+
+    // At this point, we know that allocation has succeeded, so we restore the
+    // item in the final slot.
+
+    if is_overflow {
+      unsafe { *old_b = old_b_hash };
+      self.space = old_r;
+    }
+
+    let new_t = unsafe { new_a.add(new_d - 1) };
+    let new_y = unsafe { new_w.add(new_d - 1) };
+    let new_b = unsafe { new_a.add(new_n - 1) };
+
+    let mut i = 0;
+    let mut j = 0;
+
+    while i < old_n {
+      let x = unsafe { *old_a.add(i) };
+
+      if x != 0 {
+        j = max(j, new_d - 1 - spot(new_s, x) as usize);
+        unsafe { *new_a.add(j) = x };
+        unsafe { new_w.add(j).write(old_w.add(i).read()) };
+        j += 1;
+      }
+
+      i += 1;
+    }
+
+    self.table = new_t;
+    self.slots = new_y;
+    self.shift = new_s;
+    self.space = new_r;
+    self.check = new_b;
+
+    // The map is now in a valid state, even if `dealloc` panics.
+
+    unsafe { alloc::alloc::dealloc(old_a as *mut u8, old_hashes_layout) };
+    unsafe { dealloc_values(old_w, old_values_layout) };
+  }
+
+  /// Inserts the given key and value into the map. Returns the previous value
+  /// associated with given key, if one was present.
+  ///
+  /// # Panics
+  ///
+  /// Panics when allocation fails. If that happens, it is possible for the map
+  /// to leak an arbitrary set of items, but the map will remain in a valid
+  /// state.
+
+  #[inline(always)]
+  pub fn insert(&mut self, key: NonZeroU64, value: T) -> Option<T> {
+    let t = self.table as *mut u64;
+    let m = self.mixer;
+    let h = m.hash(key).get();
+
+    if t.is_null() {
+      unsafe { self.internal_init_table_and_insert(h, value) };
+      return None;
+    }
+
+    let y = self.slots as *mut T;
+    let s = self.shift;
+
+    let mut i = - spot(s, h);
+    let mut x = unsafe { *t.offset(i) };
+
+    while x > h {
+      i += 1;
+      x = unsafe { *t.offset(i) };
+    }
+
+    if x == h {
+      let v = mem::replace(unsafe { &mut *y.offset(i) }, value);
+      return Some(v);
+    }
+
+    let mut v = value;
+
+    unsafe { *t.offset(i) = h };
+
+    while x != 0 {
+      v = mem::replace(unsafe { &mut *y.offset(i) }, v);
+      i += 1;
+      x = mem::replace(unsafe { &mut *t.offset(i) }, x);
+    }
+
+    unsafe { y.offset(i).write(v) };
+
+    let r = self.space - 1;
+    self.space = r;
+    let b = self.check as *mut u64;
+
+    if r < 0 || unsafe { t.offset(i) } == b { unsafe { self.internal_grow_table() }; }
+
+    None
+  }
+
+  /// Removes the given key from the map. Returns the previous value associated
+  /// with the given key, if one was present.
+
+  #[inline(always)]
+  pub fn remove(&mut self, key: NonZeroU64) -> Option<T> {
+    let t = self.table as *mut u64;
+
+    if t.is_null() { return None; }
+
+    let y = self.slots as *mut T;
+    let m = self.mixer;
+    let s = self.shift;
+    let h = m.hash(key).get();
+
+    let mut i = - spot(s, h);
+    let mut x = unsafe { *t.offset(i) };
+
+    while x > h {
+      i += 1;
+      x = unsafe { *t.offset(i) };
+    }
+
+    if x != h { return None; }
+
+    let v = unsafe { y.offset(i).read() };
+
+    loop {
+      let x = unsafe { *t.offset(i + 1) };
+
+      if i < - spot(s, x) || expect(x == 0, false) { break; }
+
+      unsafe { *t.offset(i) = x };
+      unsafe { y.offset(i).write(y.offset(i + 1).read()) };
+
+      i += 1;
+    }
+
+    unsafe { *t.offset(i) = 0 };
+    self.space += 1;
+
+    Some(v)
+  }
+
+  /// Removes every item from the map. Retains heap-allocated memory.
+
+  pub fn clear(&mut self) {
+    let t = self.table as *mut u64;
+
+    if t.is_null() { return; }
+
+    let y = self.slots as *mut T;
+    let s = self.shift;
+    let r = self.space;
+    let b = self.check;
+    let c = 1 << (64 - s - 1);
+    let d = 1 << (64 - s);
+    let a = unsafe { t.sub(d - 1) };
+    let k = (c - r) as usize;
+
+    if k == 0 { return; }
+
+    // As for `HashMapNZ64`, the guard empties the table when it is dropped,
+    // either after we have dropped every value or while unwinding from a
+    // panicking `drop`. In the latter case, the values not yet dropped are
+    // leaked.
+
+    struct Guard<'a, T> {
+      map: &'a mut SoaMapNZ64<T>,
+      ptr: (*mut u64, *mut u64),
+    }
+
+    impl<'a, T> Drop for Guard<'a, T> {
+      fn drop(&mut self) {
+        let (a, b) = self.ptr;
+        let s = self.map.shift;
+        let mut p = a;
+
+        while p <= b {
+          unsafe { *p = 0 };
+          p = unsafe { p.add(1) };
+        }
+
+        self.map.space = 1 << (64 - s - 1);
+      }
+    }
+
+    let g = Guard { map: self, ptr: (a, b as *mut u64) };
+
+    if mem::needs_drop::<T>() {
+      let mut i = unsafe { b.offset_from(t) };
+      let mut k = k;
+
+      loop {
+        i -= 1;
+
+        if unsafe { *t.offset(i) } != 0 {
+          k -= 1;
+          unsafe { ptr::drop_in_place(y.offset(i)) };
+          if k == 0 { break; }
+        }
+      }
+    }
+
+    drop(g);
+  }
+
+  /// Removes every item from the map. Releases heap-allocated memory.
+
+  pub fn reset(&mut self) {
+    let t = self.table as *mut u64;
+
+    if t.is_null() { return; }
+
+    let y = self.slots as *mut T;
+    let s = self.shift;
+    let b = self.check;
+    let d = 1 << (64 - s);
+    let e = unsafe { b.offset_from(t) } as usize;
+    let n = d + e;
+    let a = unsafe { t.sub(d - 1) };
+    let w = unsafe { y.sub(d - 1) };
+
+    self.table = ptr::null();
+    self.slots = ptr::null();
+    self.shift = INITIAL_S;
+    self.space = INITIAL_R;
+    self.check = ptr::null();
+
+    // Here, we have already put `self` into the valid initial state, so if a
+    // call to `drop` panics then we can just safely leak the table.
+
+    if mem::needs_drop::<T>() {
+      for i in 0 .. n {
+        if unsafe { *a.add(i) } != 0 {
+          unsafe { ptr::drop_in_place(w.add(i)) };
+        }
+      }
+    }
+
+    let (hashes_layout, values_layout) = layouts::<T>(n);
+
+    unsafe { alloc::alloc::dealloc(a as *mut u8, hashes_layout) };
+    unsafe { dealloc_values(w, values_layout) };
+  }
+
+  /// Returns an iterator yielding each key and a reference to its associated
+  /// value. The iterator item type is `(NonZeroU64, &'_ T)`.
+
+  pub fn iter(&self) -> Iter<'_, T> {
+    let m = self.mixer.invert();
+    let t = self.table;
+    let b = self.check;
+    let i = if t.is_null() { 0 } else { unsafe { b.offset_from(t) } };
+
+    Iter { len: self.len(), map: self, idx: i, rev: m }
+  }
+}
+
+impl<T> Default for SoaMapNZ64<T> {
+  #[inline(always)]
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<T> Drop for SoaMapNZ64<T> {
+  fn drop(&mut self) {
+    self.reset()
+  }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SoaMapNZ64<T> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+    let mut items = self.iter().collect::<Vec<(NonZeroU64, &T)>>();
+
+    items.sort_by_key(|x| x.0);
+
+    let mut f = f.debug_map();
+
+    for (key, value) in items.iter() {
+      let _: _ = f.entry(key, value);
+    }
+
+    f.finish()
+  }
+}
+
+/// Iterator returned by [`SoaMapNZ64::iter`].
+
+#[derive(Clone)]
+pub struct Iter<'a, T: 'a> {
+  len: usize,
+  map: &'a SoaMapNZ64<T>,
+  idx: isize,
+  rev: Mixer,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+  type Item = (NonZeroU64, &'a T);
+
+  #[inline(always)]
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.len == 0 { return None; }
+
+    let t = self.map.table;
+    let y = self.map.slots;
+    let mut i = self.idx;
+    let mut x;
+
+    loop {
+      i -= 1;
+      x = unsafe { *t.offset(i) };
+      if x != 0 { break; }
+    }
+
+    self.len -= 1;
+    self.idx = i;
+
+    let k = self.rev.hash(unsafe { NonZeroU64::new_unchecked(x) });
+    let v = unsafe { &*y.offset(i) };
+
+    Some((k, v))
+  }
+
+  #[inline(always)]
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    (self.len, Some(self.len))
+  }
+}
+
+impl<'a, T> FusedIterator for Iter<'a, T> {}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+pub mod internal {
+  //! Unstable API exposing implementation details for tests and benchmarks.
+
+  use super::*;
+
+  pub fn probe_bytes<T>(t: &SoaMapNZ64<T>, key: NonZeroU64) -> usize {
+    let p = t.table;
+
+    if p.is_null() { return 0; }
+
+    let h = t.mixer.hash(key).get();
+    let mut i = - spot(t.shift, h);
+    let mut n = 1;
+
+    while unsafe { *p.offset(i) } > h {
+      i += 1;
+      n += 1;
+    }
+
+    n * mem::size_of::<u64>()
+  }
+}
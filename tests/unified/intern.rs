@@ -0,0 +1,68 @@
+use crate::prelude::*;
+
+#[test]
+fn test_intern() {
+  let mut t = InternerNZ64::<String>::new_seeded(&mut Rng::from_u64(1));
+
+  assert!(t.is_empty());
+  assert_eq!(t.lookup(&"a".to_string()), None);
+
+  let a = t.intern("a".to_string());
+  let b = t.intern("b".to_string());
+
+  assert_eq!(a, NonZeroU64::new(1).unwrap());
+  assert_eq!(b, NonZeroU64::new(2).unwrap());
+  assert_eq!(t.intern("a".to_string()), a);
+  assert_eq!(t.intern("b".to_string()), b);
+  assert_eq!(t.len(), 2);
+
+  for i in 0 .. 10_000 {
+    let id = t.intern(i.to_string());
+    assert_eq!(t.resolve(id), Some(&i.to_string()));
+  }
+
+  assert_eq!(t.len(), 10_002);
+
+  let mut ids = (0 .. 10_000).map(|i| t.lookup(&i.to_string()).unwrap()).collect::<Vec<_>>();
+  ids.sort();
+  ids.dedup();
+
+  assert_eq!(ids.len(), 10_000);
+  assert_eq!(t.resolve(a), Some(&"a".to_string()));
+  assert_eq!(t.resolve(NonZeroU64::new(1_000_000).unwrap()), None);
+  assert_eq!(t.lookup(&"c".to_string()), None);
+}
+
+#[test]
+fn test_intern_colliding_fingerprints() {
+  // Every value hashes the same, so every value after the first has to probe
+  // past the fingerprints taken by the earlier ones.
+
+  #[derive(Debug, Eq, PartialEq)]
+  struct Collide(u64);
+
+  impl std::hash::Hash for Collide {
+    fn hash<H: std::hash::Hasher>(&self, _: &mut H) {}
+  }
+
+  let mut t = InternerNZ64::<Collide>::new_seeded(&mut Rng::from_u64(1));
+
+  for i in 1 ..= 100 {
+    assert_eq!(t.intern(Collide(i)), NonZeroU64::new(i).unwrap());
+  }
+
+  for i in 1 ..= 100 {
+    assert_eq!(t.intern(Collide(i)), NonZeroU64::new(i).unwrap());
+    assert_eq!(t.lookup(&Collide(i)), NonZeroU64::new(i));
+    assert_eq!(t.resolve(NonZeroU64::new(i).unwrap()), Some(&Collide(i)));
+  }
+
+  assert_eq!(t.len(), 100);
+  assert_eq!(t.lookup(&Collide(101)), None);
+}
+
+#[test]
+fn test_default() {
+  let t = InternerNZ64::<String>::default();
+  assert!(t.is_empty());
+}
@@ -1,2 +1,12 @@
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+
 mod prelude;
+mod intern;
 mod map;
+mod mixer;
+mod ptr;
+mod rng;
+#[cfg(feature = "serde")]
+mod serde;
+mod set;
+mod soa;
@@ -1,5 +1,14 @@
 use crate::prelude::*;
 
+// Returns a copy of the slots of the table, each holding a stored hash and a
+// value, or zero in both for an empty slot.
+
+pub(crate) fn raw_slots(t: &HashMapNZ64<u64>) -> Vec<[u64; 2]> {
+  let (p, layout) = map::internal::allocation_info(t).unwrap();
+  let p = p.as_ptr() as *const [u64; 2];
+  (0 .. layout.size() / 16).map(|i| unsafe { *p.add(i) }).collect()
+}
+
 #[test]
 fn test_basic() -> Result<(), std::fmt::Error> {
   let mut s = String::new();
@@ -195,3 +204,2426 @@ fn foo() -> Result<(), std::fmt::Error> {
 
   Ok(())
 }
+
+#[test]
+fn test_get_dyn() {
+  trait Shape {
+    fn area(&self) -> u64;
+    fn scale(&mut self, k: u64);
+  }
+
+  struct Square(u64);
+  struct Rect(u64, u64);
+
+  impl Shape for Square {
+    fn area(&self) -> u64 { self.0 * self.0 }
+    fn scale(&mut self, k: u64) { self.0 *= k; }
+  }
+
+  impl Shape for Rect {
+    fn area(&self) -> u64 { self.0 * self.1 }
+    fn scale(&mut self, k: u64) { self.0 *= k; self.1 *= k; }
+  }
+
+  let mut t = HashMapNZ64::<Box<dyn Shape>>::new();
+  let a = NonZeroU64::new(1).unwrap();
+  let b = NonZeroU64::new(2).unwrap();
+  let c = NonZeroU64::new(3).unwrap();
+
+  assert!(t.insert(a, Box::new(Square(3))).is_none());
+  assert!(t.insert(b, Box::new(Rect(2, 5))).is_none());
+
+  assert_eq!(t.get_dyn(a).map(|x| x.area()), Some(9));
+  assert_eq!(t.get_dyn(b).map(|x| x.area()), Some(10));
+  assert!(t.get_dyn(c).is_none());
+
+  t.get_dyn_mut(b).unwrap().scale(2);
+
+  assert_eq!(t.get_dyn(b).map(|x| x.area()), Some(40));
+}
+
+#[test]
+fn test_extend_from_map() {
+  let build = |seed, lo, hi, scale| {
+    let mut t = HashMapNZ64::<u64>::new_seeded(&mut Rng::from_u64(seed));
+    for i in lo ..= hi {
+      let _ = t.insert(NonZeroU64::new(i).unwrap(), scale * i);
+    }
+    t
+  };
+
+  let other = build(1, 50, 150, 100);
+
+  // same seeds, fast path
+
+  let mut x = build(1, 1, 100, 10);
+  x.extend_from_map(&other);
+
+  // different seeds, generic path
+
+  let mut y = build(2, 1, 100, 10);
+  y.extend_from_map(&other);
+
+  assert_eq!(x.len(), 150);
+  assert_eq!(format!("{:?}", x), format!("{:?}", y));
+  assert_eq!(x.keys().collect::<Vec<_>>(), build(1, 1, 150, 0).keys().collect::<Vec<_>>());
+
+  for i in 1 ..= 150 {
+    let k = NonZeroU64::new(i).unwrap();
+    assert_eq!(x.get(k), Some(&if i < 50 { 10 * i } else { 100 * i }));
+  }
+}
+
+#[test]
+fn test_merge_with() {
+  use std::panic::AssertUnwindSafe;
+
+  let k = |i: u64| NonZeroU64::new(i).unwrap();
+
+  let build = |seed, lo, hi| {
+    let mut t = HashMapNZ64::<u64>::new_seeded(&mut Rng::from_u64(seed));
+    for i in lo ..= hi {
+      let _ = t.insert(k(i), i);
+    }
+    t
+  };
+
+  let other = build(1, 700, 1300);
+
+  // same seeds, fast path, which moves the items into a new table
+
+  let mut x = build(1, 1, 1000);
+  let before = map::internal::allocation_info(&x).unwrap();
+  x.merge_with(&other, |key, a, b| { assert_eq!(key.get(), *a); *a += 10 * *b; });
+  assert_ne!(map::internal::allocation_info(&x).unwrap().0, before.0);
+
+  // the generic path, by hand
+
+  let mut y = build(1, 1, 1000);
+
+  for (key, &b) in other.iter() {
+    match y.entry(key) {
+      map::Entry::Occupied(mut o) => { *o.get_mut() += 10 * b; }
+      map::Entry::Vacant(v) => { let _ = v.insert(b); }
+    }
+  }
+
+  assert_eq!(x.len(), 1300);
+  assert!(x == y);
+  assert_eq!(map::internal::num_slots(&x), map::internal::num_slots(&y));
+  assert_eq!(raw_slots(&x), raw_slots(&y));
+
+  for i in 1 ..= 1300 {
+    assert_eq!(x.get(k(i)), Some(&if (700 ..= 1000).contains(&i) { 11 * i } else { i }));
+  }
+
+  // different seeds, generic path
+
+  let mut z = HashMapNZ64::<u64>::new_seeded(&mut Rng::from_u64(2));
+  z.merge_with(&x, |_, _, _| unreachable!());
+  z.merge_with(&other, |key, a, &b| if key.get() <= 1000 { *a -= 10 * b });
+  assert!(z == build(2, 1, 1300));
+
+  // into an empty map, where the merge allocates the table
+
+  let mut w = HashMapNZ64::<u64>::new_seeded(&mut Rng::from_u64(1));
+  w.extend_from_map(&other);
+  assert!(w == other);
+  assert_eq!(w.keys().collect::<Vec<_>>(), other.keys().collect::<Vec<_>>());
+
+  // a panicking callback leaves the map valid, keeping the items placed so far
+
+  let mut v = build(1, 1, 1000);
+  let r = std::panic::catch_unwind(AssertUnwindSafe(|| v.merge_with(&other, |key, _, _| assert!(key.get() != 800))));
+
+  assert!(r.is_err());
+  assert!(v.len() < 1300);
+  assert_eq!(v.iter().count(), v.len());
+  assert!(v.iter().all(|(key, &a)| key.get() == a && v.get(key) == Some(&a)));
+}
+
+#[test]
+fn test_pin_capacity() {
+  let mut g = Rng::from_u64(42);
+  let mut t = HashMapNZ64::<u64>::new_seeded(&mut g);
+  let mut n = 0;
+
+  assert!(t.pin_capacity(0));
+  assert!(! t.pin_capacity(1));
+
+  for _ in 0 .. 200 {
+    let m = g.bounded_u32(20) as usize;
+    let before = map::internal::allocation_info(&t);
+    let len = t.len();
+    let pinned = t.pin_capacity(m);
+
+    assert_eq!(map::internal::allocation_info(&t), before);
+    assert_eq!(t.len(), len);
+
+    for _ in 0 .. m {
+      n += 1;
+      let _ = t.insert(NonZeroU64::new(n).unwrap(), n);
+      if g.bool() && g.bool() {
+        let _ = t.remove(NonZeroU64::new(n / 2 + 1).unwrap());
+      }
+    }
+
+    if pinned {
+      assert_eq!(map::internal::allocation_info(&t), before);
+    }
+  }
+}
+
+#[test]
+fn test_get_or_try_insert_with() {
+  let mut t = HashMapNZ64::<u64>::new();
+  let key = NonZeroU64::new(7).unwrap();
+
+  assert_eq!(t.get_or_try_insert_with(key, || Err("nope")), Err("nope"));
+  assert!(! t.contains_key(key));
+  assert!(t.is_empty());
+
+  assert_eq!(t.get_or_try_insert_with(key, || Ok::<_, ()>(42)), Ok(&mut 42));
+  assert_eq!(t.get(key), Some(&42));
+
+  *t.get_or_try_insert_with(key, || Err(())).unwrap() += 1;
+  assert_eq!(t.get(key), Some(&43));
+  assert_eq!(t.len(), 1);
+}
+
+#[test]
+fn test_layout_after_churn() {
+  let mut g = Rng::from_u64(1);
+  let mut t = HashMapNZ64::<u64>::new_seeded(&mut Rng::from_u64(2));
+  let mut u = HashMapNZ64::<u64>::new_seeded(&mut Rng::from_u64(2));
+  let mut a = Vec::new();
+
+  for i in 1 ..= 400 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i);
+    a.push(i);
+  }
+
+  for i in 401 ..= 2000 {
+    let j = g.bounded_u32(a.len() as u32 - 1) as usize;
+    let _ = t.remove(NonZeroU64::new(a[j]).unwrap());
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i);
+    a[j] = i;
+  }
+
+  for &i in a.iter() {
+    let _ = u.insert(NonZeroU64::new(i).unwrap(), i);
+  }
+
+  let hashes = |t: &HashMapNZ64<u64>| raw_slots(t).iter().map(|x| x[0]).collect::<Vec<_>>();
+
+  assert_eq!(map::internal::num_slots(&t), map::internal::num_slots(&u));
+  assert_eq!(hashes(&t), hashes(&u));
+}
+
+#[test]
+fn test_canonical_layout() {
+  let mut g = Rng::from_u64(3);
+  let mut t = HashMapNZ64::<u64>::new_seeded(&mut Rng::from_u64(4));
+  let mut a = Vec::new();
+
+  for i in 1 ..= 3000 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i);
+    a.push(i);
+
+    if g.bool() {
+      let j = g.bounded_u32(a.len() as u32 - 1) as usize;
+      let _ = t.remove(NonZeroU64::new(a.swap_remove(j)).unwrap());
+    }
+  }
+
+  let hashes = raw_slots(&t).iter().map(|x| x[0]).collect::<Vec<_>>();
+
+  let c = t.capacity();
+  assert!(c.is_power_of_two() && c > t.len());
+
+  let d = 2 * c;
+  let s = 64 - d.trailing_zeros() as usize;
+
+  // The items are sorted by decreasing hash, and each one sits in the first
+  // free slot at or after its ideal slot, so none of them can move closer.
+
+  let mut q = 0;
+  let mut prev = u64::MAX;
+
+  for (i, &x) in hashes.iter().enumerate() {
+    if x != 0 {
+      assert!(x < prev);
+      q = q.max(d - 1 - map::internal::spot(s, x) as usize);
+      assert_eq!(i, q);
+      q += 1;
+      prev = x;
+    }
+  }
+
+  assert_eq!(hashes.iter().filter(|&&x| x != 0).count(), t.len());
+}
+
+#[test]
+fn test_join() {
+  let mut t = HashMapNZ64::<u64>::new();
+  let mut u = HashMapNZ64::<&'static str>::new();
+
+  for i in 1 ..= 30 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), 10 * i);
+  }
+
+  for i in (3 ..= 60).step_by(3) {
+    let _ = u.insert(NonZeroU64::new(i).unwrap(), if i % 2 == 0 { "even" } else { "odd" });
+  }
+
+  let mut a = t.join(&u).map(|(k, &x, &y)| (k.get(), x, y)).collect::<Vec<_>>();
+  let mut b = u.join(&t).map(|(k, &y, &x)| (k.get(), x, y)).collect::<Vec<_>>();
+  a.sort();
+  b.sort();
+
+  let c =
+    (3 ..= 30).step_by(3)
+      .map(|i| (i, 10 * i, if i % 2 == 0 { "even" } else { "odd" }))
+      .collect::<Vec<_>>();
+
+  assert_eq!(a, c);
+  assert_eq!(b, c);
+  assert_eq!(t.join(&HashMapNZ64::<()>::new()).count(), 0);
+}
+
+#[test]
+fn test_left_join() {
+  let mut t = HashMapNZ64::<u64>::new();
+  let mut u = HashMapNZ64::<u64>::new();
+
+  for i in 1 ..= 10 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i);
+  }
+
+  for i in 6 ..= 15 {
+    let _ = u.insert(NonZeroU64::new(i).unwrap(), 100 * i);
+  }
+
+  let it = t.left_join(&u);
+
+  assert_eq!(it.len(), 10);
+
+  let mut a = it.map(|(k, &x, y)| (k.get(), x, y.copied())).collect::<Vec<_>>();
+  a.sort();
+
+  let b =
+    (1 ..= 10)
+      .map(|i| (i, i, if i >= 6 { Some(100 * i) } else { None }))
+      .collect::<Vec<_>>();
+
+  assert_eq!(a, b);
+}
+
+#[cfg(debug_assertions)]
+#[test]
+fn test_remove_poisons_slot() {
+  let mut t = HashMapNZ64::<u64>::new();
+
+  for i in 1 ..= 3 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i);
+  }
+
+  let _ = t.remove(NonZeroU64::new(2).unwrap());
+
+  let a = raw_slots(&t);
+
+  assert_eq!(a.iter().filter(|x| x[0] != 0).count(), 2);
+  assert_eq!(a.iter().filter(|x| x[0] == 0 && x[1] == 0xa5a5_a5a5_a5a5_a5a5).count(), 1);
+}
+
+#[test]
+fn test_iter_rev() {
+  let mut t = HashMapNZ64::<u64>::new();
+
+  assert_eq!(t.iter_rev().count(), 0);
+
+  for i in 1 ..= 100 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), 10 * i);
+  }
+
+  let it = t.iter_rev();
+
+  assert_eq!(it.len(), t.len());
+
+  let mut a = it.collect::<Vec<_>>();
+  a.reverse();
+
+  assert_eq!(a, t.iter().collect::<Vec<_>>());
+}
+
+#[test]
+fn test_seed() {
+  let mut t = HashMapNZ64::<u64>::new();
+
+  for i in 1 ..= 100 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i);
+  }
+
+  let mut u = HashMapNZ64::<u64>::new_with_mixer(Mixer::new(t.seed()));
+
+  for i in (1 ..= 100).rev() {
+    let _ = u.insert(NonZeroU64::new(i).unwrap(), i);
+  }
+
+  assert_eq!(u.mixer(), t.mixer());
+  assert_eq!(u.keys().collect::<Vec<_>>(), t.keys().collect::<Vec<_>>());
+
+  let slot_of = |t: &HashMapNZ64<u64>, key: NonZeroU64| {
+    let (p, _) = map::internal::allocation_info(t).unwrap();
+    let x = t.get(key).unwrap() as *const u64 as usize;
+    x - p.as_ptr() as usize
+  };
+
+  let key = NonZeroU64::new(37).unwrap();
+
+  assert_eq!(slot_of(&t, key), slot_of(&u, key));
+}
+
+#[test]
+fn test_iter_matching() {
+  let mut t = HashMapNZ64::<u64>::new();
+
+  for i in (2 ..= 20).step_by(2) {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), 10 * i);
+  }
+
+  let keys = [1, 2, 3, 4, 10, 15, 20, 25].map(|i| NonZeroU64::new(i).unwrap());
+  let a = t.iter_matching(&keys).map(|(k, &v)| (k.get(), v)).collect::<Vec<_>>();
+
+  assert_eq!(a, vec![(2, 20), (4, 40), (10, 100), (20, 200)]);
+}
+
+#[test]
+fn test_value_sum_and_mean() {
+  let mut t = HashMapNZ64::<u64>::new();
+  let mut u = HashMapNZ64::<f64>::new();
+
+  assert_eq!(t.value_sum(), 0);
+  assert_eq!(t.value_mean(), None);
+  assert_eq!(u.value_sum(), 0.);
+  assert_eq!(u.value_mean(), None);
+
+  for i in 1 ..= 100 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i);
+    let _ = u.insert(NonZeroU64::new(i).unwrap(), i as f64 / 4.);
+  }
+
+  assert_eq!(t.value_sum(), 5050);
+  assert_eq!(t.value_mean(), Some(50.5));
+  assert_eq!(u.value_sum(), 1262.5);
+  assert_eq!(u.value_mean(), Some(12.625));
+
+  let _ = t.insert(NonZeroU64::new(1).unwrap(), u64::MAX);
+
+  assert_eq!(t.value_sum(), 5048);
+  assert_eq!(t.value_mean(), Some((u64::MAX as u128 + 5049) as f64 / 100.));
+}
+
+#[test]
+fn test_debug_verbose() -> Result<(), std::fmt::Error> {
+  let mut s = String::new();
+  let mut t = HashMapNZ64::<u64>::new_seeded(&mut Rng::from_u64(1));
+
+  writeln!(s, "{:?}", t.debug_verbose())?;
+
+  for i in 1 ..= 3 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), 10 * i);
+  }
+
+  writeln!(s, "{:?}", t)?;
+  writeln!(s, "{:#?}", t)?;
+  writeln!(s, "{:?}", t.debug_verbose())?;
+  writeln!(s, "{:#?}", t.debug_verbose())?;
+
+  expect![[r#"
+      {} # len = 0, load = 0.000, max probe = 0
+      {1: 10, 2: 20, 3: 30}
+      {
+          1: 10,
+          2: 20,
+          3: 30,
+      }
+      {1: 10, 2: 20, 3: 30} # len = 3, load = 0.125, max probe = 0
+      {
+          1: 10,
+          2: 20,
+          3: 30,
+      }
+      len = 3, load = 0.125, max probe = 0
+  "#]].assert_eq(&s);
+
+  Ok(())
+}
+
+#[test]
+fn test_spot_in_bounds() {
+  let mut g = Rng::from_u64(42);
+
+  for s in 1 ..= 60 {
+    let d = 1u64 << (64 - s);
+
+    for h in [1, 2, u64::MAX, u64::MAX >> 1, 1 << 63].into_iter().chain((0 .. 1000).map(|_| g.u64() | 1)) {
+      let i = map::internal::spot(s, h);
+      assert!(0 <= i && (i as u64) < d);
+    }
+  }
+}
+
+#[test]
+fn test_partitioned() {
+  let mut t = HashMapNZ64::<u64>::new();
+
+  for i in 1 ..= 1000 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), 10 * i);
+  }
+
+  let a = t.partitioned(2);
+
+  assert_eq!(a.len(), 4);
+  assert!(a.iter().all(|x| ! x.is_empty()));
+
+  for (i, x) in a.iter().enumerate() {
+    for &(k, _) in x.iter() {
+      assert_eq!((t.mixer().hash(k).get() >> 62) as usize, i);
+    }
+  }
+
+  let mut b = a.iter().flatten().copied().collect::<Vec<_>>();
+  let mut c = t.iter().collect::<Vec<_>>();
+  b.sort();
+  c.sort();
+
+  assert_eq!(b, c);
+  assert_eq!(t.partitioned(0).len(), 1);
+  assert_eq!(t.partitioned(0)[0].len(), 1000);
+  assert_eq!(t.partitioned(16).len(), 1 << 16);
+  assert!(std::panic::catch_unwind(|| t.partitioned(17)).is_err());
+}
+
+#[test]
+fn test_get_or_cow() {
+  let mut t = HashMapNZ64::<String>::new();
+  let a = NonZeroU64::new(1).unwrap();
+  let b = NonZeroU64::new(2).unwrap();
+  let default = String::from("default");
+
+  let _ = t.insert(a, String::from("stored"));
+
+  assert_eq!(t.get_or_cow(a, &default), "stored");
+  assert!(std::ptr::eq(t.get_or_cow(a, &default), t.get(a).unwrap()));
+  assert!(std::ptr::eq(t.get_or_cow(b, &default), &default));
+  assert_eq!(t.len(), 1);
+}
+
+#[test]
+fn test_iterators_are_send_and_sync() {
+  fn is_send<T: Send>() {}
+  fn is_sync<T: Sync>() {}
+  fn check<T: Send + Sync>() {
+    is_send::<map::Iter<'_, T>>();
+    is_sync::<map::Iter<'_, T>>();
+    is_send::<map::IterRev<'_, T>>();
+    is_sync::<map::IterRev<'_, T>>();
+    is_send::<map::IterMut<'_, T>>();
+    is_sync::<map::IterMut<'_, T>>();
+    is_send::<map::Keys<'_, T>>();
+    is_sync::<map::Keys<'_, T>>();
+    is_send::<map::Values<'_, T>>();
+    is_sync::<map::Values<'_, T>>();
+    is_send::<map::ValuesMut<'_, T>>();
+    is_sync::<map::ValuesMut<'_, T>>();
+    is_send::<map::IntoIter<T>>();
+    is_sync::<map::IntoIter<T>>();
+    is_send::<map::IntoValues<T>>();
+    is_sync::<map::IntoValues<T>>();
+    is_send::<map::Join<'_, T, T>>();
+    is_sync::<map::Join<'_, T, T>>();
+    is_send::<map::LeftJoin<'_, T, T>>();
+    is_sync::<map::LeftJoin<'_, T, T>>();
+    is_send::<map::Matching<'_, T>>();
+    is_sync::<map::Matching<'_, T>>();
+    is_send::<map::Entry<'_, T>>();
+    is_sync::<map::Entry<'_, T>>();
+  }
+
+  check::<u64>();
+  check::<String>();
+}
+
+#[test]
+fn test_reserve_for_transaction() {
+  // Keys with tiny hashes all want the last regular slot, so inserting them
+  // pushes items into the extra slots at the end of the table.
+
+  let colliding = |t: &HashMapNZ64<u64>, n: u64| {
+    let m = t.mixer();
+    (1 ..= n).map(|i| m.unhash(NonZeroU64::new(i).unwrap())).collect::<Vec<_>>()
+  };
+
+  let spread = |t: &mut HashMapNZ64<u64>| {
+    for i in 1 ..= 64 {
+      let _ = t.insert(NonZeroU64::new(i << 40).unwrap(), i);
+    }
+    for i in 1 ..= 64 {
+      let _ = t.remove(NonZeroU64::new(i << 40).unwrap());
+    }
+  };
+
+  // Having room for plenty of items doesn't stop an overflow.
+
+  let mut t = HashMapNZ64::<u64>::new_seeded(&mut Rng::from_u64(1));
+  spread(&mut t);
+  let keys = colliding(&t, 20);
+  let n = map::internal::num_slots(&t);
+
+  for &k in keys.iter() {
+    let _ = t.insert(k, k.get());
+  }
+
+  assert!(map::internal::num_slots(&t) > n);
+
+  // But reserving for the transaction does.
+
+  let mut t = HashMapNZ64::<u64>::new_seeded(&mut Rng::from_u64(1));
+  spread(&mut t);
+  let keys = colliding(&t, 20);
+  t.reserve_for_transaction(keys.len());
+  assert!(t.pin_capacity(keys.len()));
+  let n = map::internal::num_slots(&t);
+  let a = map::internal::allocation_info(&t).unwrap().0;
+
+  for (i, &k) in keys.iter().enumerate() {
+    let _ = t.insert(k, k.get());
+    if i % 3 == 0 { let _ = t.remove(keys[i / 2]); }
+  }
+
+  assert_eq!(map::internal::num_slots(&t), n);
+  assert_eq!(map::internal::allocation_info(&t).unwrap().0, a);
+
+  // Reserving on an empty map allocates a table.
+
+  let mut t = HashMapNZ64::<u64>::new();
+  t.reserve_for_transaction(0);
+  assert_eq!(map::internal::num_slots(&t), 0);
+  t.reserve_for_transaction(100);
+  assert!(t.pin_capacity(100));
+  assert!(t.is_empty());
+}
+
+#[test]
+fn test_zeroize() {
+  let secret = 0x5ec2_e75e_c2e7_5ec2;
+  let mut t = HashMapNZ64::<u64>::new_seeded(&mut Rng::from_u64(1));
+
+  for i in 1 ..= 6 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), secret + i);
+  }
+
+  for i in 1 ..= 3 {
+    assert_eq!(t.remove_zeroize(NonZeroU64::new(i).unwrap()), Some(secret + i));
+  }
+
+  assert_eq!(t.remove_zeroize(NonZeroU64::new(1).unwrap()), None);
+  assert_eq!(t.len(), 3);
+
+  let a = raw_slots(&t);
+
+  assert!(a.iter().all(|x| (x[0] == 0) == (x[1] == 0)));
+  assert_eq!(a.iter().filter(|x| x[1] != 0).count(), 3);
+
+  for i in 4 ..= 6 {
+    assert_eq!(t.get(NonZeroU64::new(i).unwrap()), Some(&(secret + i)));
+  }
+
+  t.clear_zeroize();
+
+  assert!(t.is_empty());
+  assert!(raw_slots(&t).iter().all(|x| *x == [0, 0]));
+
+  let mut t = HashMapNZ64::<u64>::new();
+  t.clear_zeroize();
+  assert_eq!(t.remove_zeroize(NonZeroU64::new(1).unwrap()), None);
+}
+
+#[test]
+fn test_apply_sorted() {
+  let mut g = Rng::from_u64(1);
+  let mut t = HashMapNZ64::<u64>::new();
+  let mut u = HashMapNZ64::<u64>::new();
+
+  for i in 1 ..= 100 {
+    let _ = t.insert(NonZeroU64::new(3 * i).unwrap(), i);
+    let _ = u.insert(NonZeroU64::new(3 * i).unwrap(), i);
+  }
+
+  let mut keys = (0 .. 500).map(|_| NonZeroU64::new(1 + g.bounded_u32(599) as u64).unwrap()).collect::<Vec<_>>();
+  keys.sort();
+
+  let op = |key: NonZeroU64, e: map::Entry<'_, u64>| {
+    match e {
+      map::Entry::Occupied(mut o) => {
+        if key.get().is_multiple_of(2) {
+          *o.get_mut() += key.get();
+        } else {
+          let _ = o.remove();
+        }
+      }
+      map::Entry::Vacant(v) => {
+        let _ = v.insert(key.get());
+      }
+    }
+  };
+
+  t.apply_sorted(&keys, op);
+
+  for &key in keys.iter() {
+    op(key, u.entry(key));
+  }
+
+  let mut a = t.iter().map(|(k, &v)| (k, v)).collect::<Vec<_>>();
+  let mut b = u.iter().map(|(k, &v)| (k, v)).collect::<Vec<_>>();
+  a.sort();
+  b.sort();
+
+  assert_eq!(a, b);
+}
+
+#[test]
+fn test_iter_mut() {
+  let mut t = HashMapNZ64::<u64>::new();
+
+  for i in 1 ..= 100 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i);
+  }
+
+  let it = t.iter_mut();
+
+  assert_eq!(it.len(), 100);
+
+  let mut n = 0;
+
+  for (k, v) in it {
+    assert_eq!(*v, k.get());
+    *v *= 2;
+    n += 1;
+  }
+
+  assert_eq!(n, 100);
+
+  for i in 1 ..= 100 {
+    assert_eq!(t.get(NonZeroU64::new(i).unwrap()), Some(&(2 * i)));
+  }
+
+  let mut a = t.iter_mut().map(|(_, v)| v as *mut u64).collect::<Vec<_>>();
+  a.sort();
+  a.dedup();
+
+  assert_eq!(a.len(), 100);
+}
+
+#[test]
+fn test_checked_key() {
+  assert!(! wordmap::is_valid_key(0));
+  assert!(wordmap::is_valid_key(1));
+  assert!(wordmap::is_valid_key(u64::MAX));
+
+  assert_eq!(wordmap::checked_key(0), None);
+  assert_eq!(wordmap::checked_key(1), NonZeroU64::new(1));
+  assert_eq!(wordmap::checked_key(u64::MAX), NonZeroU64::new(u64::MAX));
+
+  assert!(map::is_valid_key(7));
+  assert_eq!(map::checked_key(7), NonZeroU64::new(7));
+
+  let mut t = HashMapNZ64::<u64>::new();
+  let _ = t.insert(wordmap::checked_key(5).unwrap(), 5);
+  assert_eq!(wordmap::checked_key(5).and_then(|k| t.get(k)), Some(&5));
+  assert_eq!(wordmap::checked_key(0).and_then(|k| t.get(k)), None);
+}
+
+#[test]
+fn test_slot_index_of() {
+  let mut t = HashMapNZ64::<u64>::new();
+
+  assert_eq!(t.slot_index_of(NonZeroU64::new(1).unwrap()), None);
+
+  for i in 1 ..= 100 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i);
+  }
+
+  let a = raw_slots(&t);
+
+  assert_eq!(a.len(), map::internal::num_slots(&t));
+
+  let mut seen = Vec::new();
+
+  for i in 1 ..= 100 {
+    let k = NonZeroU64::new(i).unwrap();
+    let j = t.slot_index_of(k).unwrap();
+    assert_eq!(a[j], [t.mixer().hash(k).get(), i]);
+    seen.push(j);
+  }
+
+  seen.sort();
+  seen.dedup();
+
+  assert_eq!(seen.len(), 100);
+
+  for i in 101 ..= 200 {
+    assert_eq!(t.slot_index_of(NonZeroU64::new(i).unwrap()), None);
+  }
+}
+
+// The global allocator for the tests, which counts the allocations made by
+// each thread, so that a test can check that some operation allocates nothing
+// beyond what it must. It also fails them on the current thread while
+// `FAIL_ALLOC` is set.
+
+struct TestAlloc;
+
+std::thread_local! {
+  static ALLOCS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+fn allocs() -> usize {
+  ALLOCS.with(|n| n.get())
+}
+
+fn count_alloc() {
+  let _ = ALLOCS.try_with(|n| n.set(n.get() + 1));
+}
+
+unsafe impl std::alloc::GlobalAlloc for TestAlloc {
+  unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+    if fail_alloc() { return std::ptr::null_mut(); }
+    count_alloc();
+    unsafe { std::alloc::System.alloc(layout) }
+  }
+
+  unsafe fn alloc_zeroed(&self, layout: std::alloc::Layout) -> *mut u8 {
+    if fail_alloc() { return std::ptr::null_mut(); }
+    count_alloc();
+    unsafe { std::alloc::System.alloc_zeroed(layout) }
+  }
+
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+    unsafe { std::alloc::System.dealloc(ptr, layout) }
+  }
+}
+
+#[global_allocator]
+static ALLOC: TestAlloc = TestAlloc;
+
+#[test]
+fn test_clone() {
+  let items = |t: &HashMapNZ64<String>| {
+    let mut a = t.iter().map(|(k, v)| (k, v.clone())).collect::<Vec<_>>();
+    a.sort();
+    a
+  };
+
+  let mut t = HashMapNZ64::<String>::new();
+
+  assert!(items(&t.clone()).is_empty());
+
+  for i in 1 ..= 100 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i.to_string());
+  }
+
+  let u = t.clone();
+
+  assert_eq!(u.mixer(), t.mixer());
+  assert_eq!(items(&u), items(&t));
+  assert_eq!(map::internal::num_slots(&u), map::internal::num_slots(&t));
+
+  // With the same mixer and shape, `clone_from` reuses the allocation.
+
+  let mut v = u.clone();
+  let _ = v.insert(NonZeroU64::new(1000).unwrap(), "x".to_string());
+  let _ = t.remove(NonZeroU64::new(1).unwrap());
+  let _ = t.insert(NonZeroU64::new(2).unwrap(), "y".to_string());
+  let a = map::internal::allocation_info(&v).unwrap().0;
+  let n = allocs();
+
+  v.clone_from(&t);
+
+  // Each value's clone allocates once, and nothing else does.
+
+  assert_eq!(allocs() - n, t.len());
+  assert_eq!(map::internal::allocation_info(&v).unwrap().0, a);
+  assert_eq!(items(&v), items(&t));
+  assert_eq!(v.get(NonZeroU64::new(2).unwrap()), Some(&"y".to_string()));
+
+  // Otherwise, it falls back to clearing and reinserting.
+
+  let mut w = HashMapNZ64::<String>::new();
+  let _ = w.insert(NonZeroU64::new(5000).unwrap(), "z".to_string());
+  let n = allocs();
+
+  w.clone_from(&t);
+
+  assert!(allocs() - n > t.len());
+
+  assert_eq!(w.mixer(), t.mixer());
+  assert_eq!(items(&w), items(&t));
+
+  for i in 1 ..= 200 {
+    let k = NonZeroU64::new(i).unwrap();
+    assert_eq!(w.get(k), t.get(k));
+    assert_eq!(v.get(k), t.get(k));
+  }
+
+  let mut x = HashMapNZ64::<String>::new();
+  x.clone_from(&HashMapNZ64::new());
+  assert!(x.is_empty());
+}
+
+#[test]
+fn test_needs_rehash() {
+  let mut g = Rng::from_u64(1);
+
+  let mut t = HashMapNZ64::<u64>::new();
+  assert!(! t.needs_rehash());
+
+  for _ in 0 .. 10_000 {
+    let _ = t.insert(NonZeroU64::new(g.u64() | 1).unwrap(), 0);
+  }
+
+  assert!(! t.needs_rehash());
+
+  // Keys whose hashes share their high bits all compete for the same slots.
+
+  let mut u = HashMapNZ64::<u64>::new_seeded(&mut g);
+  let m = u.mixer();
+
+  for i in 1 ..= 1000 {
+    let _ = u.insert(m.unhash(NonZeroU64::new(i).unwrap()), 0);
+  }
+
+  assert!(u.needs_rehash());
+
+  let mut v = HashMapNZ64::<u64>::new_seeded(&mut g);
+  v.extend_from_map(&u);
+
+  assert!(! v.needs_rehash());
+}
+
+#[test]
+fn test_from_columns() {
+  let keys = (1 ..= 100).chain(1 ..= 10).map(|i| NonZeroU64::new(i).unwrap()).collect::<Vec<_>>();
+  let values = (0 .. keys.len() as u64).collect::<Vec<_>>();
+
+  let t = HashMapNZ64::from_columns(&keys, &values);
+
+  assert_eq!(t.len(), 100);
+
+  for i in 1 ..= 10 {
+    assert_eq!(t.get(NonZeroU64::new(i).unwrap()), Some(&(99 + i)));
+  }
+
+  for i in 11 ..= 100 {
+    assert_eq!(t.get(NonZeroU64::new(i).unwrap()), Some(&(i - 1)));
+  }
+
+  let u = HashMapNZ64::from_columns_owned(keys.clone(), values.iter().map(|x| x.to_string()).collect());
+
+  assert_eq!(u.len(), 100);
+
+  for (k, v) in t.iter() {
+    assert_eq!(u.get(k), Some(&v.to_string()));
+  }
+
+  let v = HashMapNZ64::<u64>::from_columns(&[], &[]);
+
+  assert!(v.is_empty());
+  assert_eq!(map::internal::num_slots(&v), 0);
+
+  assert!(std::panic::catch_unwind(|| HashMapNZ64::from_columns(&keys[1 ..], &values)).is_err());
+  assert!(std::panic::catch_unwind(|| HashMapNZ64::from_columns_owned(keys.clone(), vec![0u64])).is_err());
+}
+
+#[test]
+fn test_peek() {
+  let mut t = HashMapNZ64::<u64>::new();
+
+  assert_eq!(t.peek(NonZeroU64::new(1).unwrap()), None);
+
+  for i in 1 ..= 100 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i);
+  }
+
+  for i in 1 ..= 200 {
+    let k = NonZeroU64::new(i).unwrap();
+    assert_eq!(t.peek(k), t.get(k));
+  }
+
+  assert_eq!(t.peek(NonZeroU64::new(7).unwrap()), Some(&7));
+}
+
+#[test]
+fn test_remove_where_collect() {
+  let mut g = Rng::from_u64(1);
+  let mut t = HashMapNZ64::<u64>::new();
+  let mut stale = Vec::new();
+
+  assert!(t.remove_where_collect(|_| true).is_empty());
+
+  for i in 1 ..= 1000 {
+    let v = g.bounded_u32(9) as u64;
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), v);
+    if v < 3 { stale.push(NonZeroU64::new(i).unwrap()); }
+  }
+
+  let a = t.remove_where_collect(|&v| v < 3);
+
+  assert_eq!(&*a, &*stale);
+  assert_eq!(t.len(), 1000 - stale.len());
+
+  for i in 1 ..= 1000 {
+    let k = NonZeroU64::new(i).unwrap();
+    match t.get(k) {
+      None => assert!(stale.contains(&k)),
+      Some(&v) => assert!(v >= 3 && ! stale.contains(&k)),
+    }
+  }
+
+  assert!(t.remove_where_collect(|&v| v < 3).is_empty());
+}
+
+#[test]
+fn test_grow_to() {
+  let mut t = HashMapNZ64::<u64>::new_seeded(&mut Rng::from_u64(1));
+
+  t.grow_to(0);
+  assert_eq!(map::internal::num_slots(&t), 0);
+
+  t.grow_to(1000);
+  let n = map::internal::num_slots(&t);
+  let a = map::internal::allocation_info(&t).unwrap().0;
+
+  assert!(n >= 2000);
+  assert!(t.is_empty());
+
+  for i in 1 ..= 1000 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i);
+  }
+
+  assert_eq!(map::internal::num_slots(&t), n);
+  assert_eq!(map::internal::allocation_info(&t).unwrap().0, a);
+
+  t.grow_to(10);
+  assert_eq!(map::internal::num_slots(&t), n);
+
+  t.grow_to(5000);
+  assert!(map::internal::num_slots(&t) >= 10000);
+  assert_eq!(t.len(), 1000);
+
+  for i in 1 ..= 1000 {
+    assert_eq!(t.get(NonZeroU64::new(i).unwrap()), Some(&i));
+  }
+}
+
+#[test]
+fn test_remove_before_check() {
+  // Keys with tiny hashes all want the last regular slot, so eight of them
+  // fill every slot from there up to the one just before the final slot.
+
+  let mut t = HashMapNZ64::<u64>::new_seeded(&mut Rng::from_u64(1));
+  let m = t.mixer();
+  let key = |h: u64| m.unhash(NonZeroU64::new(h).unwrap());
+
+  for h in 1 ..= 8 {
+    let _ = t.insert(key(h), h);
+  }
+
+  let n = map::internal::num_slots(&t);
+  let last = t.slot_index_of(key(1)).unwrap();
+
+  assert_eq!(last, n - 2);
+
+  // The item just before the final slot.
+
+  assert_eq!(t.remove(key(1)), Some(1));
+  assert_eq!(t.slot_index_of(key(2)), Some(n - 3));
+
+  // An item in the middle of the cluster, shifting the rest back.
+
+  assert_eq!(t.remove(key(6)), Some(6));
+  assert_eq!(t.slot_index_of(key(2)), Some(n - 4));
+
+  for h in [2, 3, 4, 5, 7, 8] {
+    assert_eq!(t.get(key(h)), Some(&h));
+  }
+
+  for h in [1, 6] {
+    let _ = t.insert(key(h), h);
+  }
+
+  assert_eq!(t.slot_index_of(key(1)), Some(last));
+  assert_eq!(map::internal::num_slots(&t), n);
+}
+
+#[test]
+fn test_epoch() {
+  let mut t = HashMapNZ64::<u64>::new();
+  let mut e = t.epoch();
+
+  assert_eq!(e, 0);
+  assert_eq!(t.remove(NonZeroU64::new(1).unwrap()), None);
+  t.clear();
+  assert_eq!(t.epoch(), e);
+
+  for i in 1 ..= 1000 {
+    let n = map::internal::num_slots(&t);
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i);
+    let grew = (map::internal::num_slots(&t) != n && n != 0) as u64;
+    assert_eq!(t.epoch(), e + 1 + grew);
+    e = t.epoch();
+  }
+
+  for i in 1 ..= 1000 {
+    let k = NonZeroU64::new(i).unwrap();
+    let _ = t.get(k);
+    let _ = t.contains_key(k);
+    *t.get_mut(k).unwrap() += 1;
+    let _ = t.insert(k, i);
+  }
+
+  let _ = t.iter().count();
+  let _ = t.values_mut().count();
+
+  assert_eq!(t.epoch(), e);
+
+  for i in 1 ..= 500 {
+    assert_eq!(t.remove(NonZeroU64::new(i).unwrap()), Some(i));
+    assert_eq!(t.epoch(), e + 1);
+    e = t.epoch();
+  }
+
+  assert_eq!(t.remove(NonZeroU64::new(1).unwrap()), None);
+  assert_eq!(t.epoch(), e);
+
+  t.clear();
+  assert_eq!(t.epoch(), e + 1);
+  e = t.epoch();
+
+  assert_eq!(t.drain().count(), 0);
+  assert_eq!(t.epoch(), e);
+
+  for i in 1 ..= 10 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i);
+  }
+
+  e = t.epoch();
+  assert_eq!(t.drain().take(3).count(), 3);
+  assert!(t.is_empty());
+  assert_eq!(t.epoch(), e + 1);
+}
+
+#[test]
+fn test_from_iter_keep() {
+  let mut g = Rng::from_u64(1);
+  let mut items = Vec::new();
+  let mut best = [0; 101];
+
+  for _ in 0 .. 10_000 {
+    let k = 1 + g.bounded_u32(99) as usize;
+    let v = g.u64();
+    best[k] = best[k].max(v);
+    items.push((NonZeroU64::new(k as u64).unwrap(), v));
+  }
+
+  let t = HashMapNZ64::from_iter_keep(items.iter().copied(), |e, c| c > e);
+
+  assert_eq!(t.len(), 100);
+
+  for (k, &v) in best.iter().enumerate().skip(1) {
+    assert_eq!(t.get(NonZeroU64::new(k as u64).unwrap()), Some(&v));
+  }
+
+  let u = HashMapNZ64::from_iter_keep(items.iter().copied(), |_, _| false);
+
+  for &(k, _) in items.iter() {
+    assert_eq!(u.get(k), Some(&items.iter().find(|x| x.0 == k).unwrap().1));
+  }
+}
+
+#[test]
+fn test_full_join() {
+  let mut t = HashMapNZ64::<u64>::new();
+  let mut u = HashMapNZ64::<String>::new();
+
+  assert_eq!(t.full_join(&u).count(), 0);
+
+  for i in 1 ..= 100 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i);
+  }
+
+  for i in 51 ..= 200 {
+    let _ = u.insert(NonZeroU64::new(i).unwrap(), i.to_string());
+  }
+
+  let mut a = t.full_join(&u).collect::<Vec<_>>();
+  a.sort_by_key(|x| x.0);
+
+  assert_eq!(a.len(), 200);
+
+  for (i, &(k, x, y)) in a.iter().enumerate() {
+    let i = i as u64 + 1;
+    assert_eq!(k.get(), i);
+    assert_eq!(x, if i <= 100 { Some(&i) } else { None });
+    assert_eq!(y.cloned(), if i > 50 { Some(i.to_string()) } else { None });
+  }
+
+  assert_eq!(u.full_join(&t).count(), 200);
+  assert_eq!(t.full_join(&HashMapNZ64::<()>::new()).count(), 100);
+  assert_eq!(HashMapNZ64::<()>::new().full_join(&u).count(), 150);
+}
+
+#[test]
+fn test_shrinkable_bytes() {
+  let mut g = Rng::from_u64(1);
+  let mut t = HashMapNZ64::<u64>::new_seeded(&mut g);
+
+  assert_eq!(t.shrinkable_bytes(), 0);
+
+  for i in 1 ..= 10_000 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i);
+  }
+
+  assert_eq!(t.shrinkable_bytes(), 0);
+
+  for i in 1 ..= 9_500 {
+    let _ = t.remove(NonZeroU64::new(i).unwrap());
+  }
+
+  let x = map::internal::num_bytes(&t);
+  let y = t.shrinkable_bytes();
+
+  assert!(y > 0);
+
+  t.shrink_to_fit();
+
+  assert_eq!(map::internal::num_bytes(&t), x - y);
+  assert_eq!(t.shrinkable_bytes(), 0);
+  assert_eq!(t.len(), 500);
+
+  for i in 1 ..= 10_000 {
+    let k = NonZeroU64::new(i).unwrap();
+    assert_eq!(t.get(k), if i > 9_500 { Some(&i) } else { None });
+  }
+
+  // Keys that cluster at the end of the table need extra slots even in the
+  // smaller table.
+
+  let mut u = HashMapNZ64::<u64>::new_seeded(&mut g);
+  let m = u.mixer();
+
+  for i in 1 ..= 1000 {
+    let _ = u.insert(NonZeroU64::new(i << 32).unwrap(), i);
+  }
+
+  for i in 1 ..= 40 {
+    let _ = u.insert(m.unhash(NonZeroU64::new(i).unwrap()), i);
+  }
+
+  for i in 1 ..= 1000 {
+    let _ = u.remove(NonZeroU64::new(i << 32).unwrap());
+  }
+
+  let x = map::internal::num_bytes(&u);
+  let y = u.shrinkable_bytes();
+
+  u.shrink_to_fit();
+
+  assert_eq!(map::internal::num_bytes(&u), x - y);
+  assert_eq!(u.len(), 40);
+
+  for i in 1 ..= 40 {
+    assert_eq!(u.get(m.unhash(NonZeroU64::new(i).unwrap())), Some(&i));
+  }
+
+  let _ = u.insert(NonZeroU64::new(7).unwrap(), 7);
+  assert_eq!(u.len(), 41);
+}
+
+#[test]
+fn test_partition_ref() {
+  let mut t = HashMapNZ64::<String>::new();
+
+  for i in 1 ..= 1000 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i.to_string());
+  }
+
+  let (a, b) = t.partition_ref(|k, v| k.get() % 3 == 0 || v.ends_with('7'));
+
+  assert_eq!(t.len(), 1000);
+  assert_eq!(a.len() + b.len(), 1000);
+  assert_eq!(a.mixer(), t.mixer());
+
+  for (k, v) in t.iter() {
+    let p = k.get() % 3 == 0 || v.ends_with('7');
+    assert_eq!(p, a.contains_key(k));
+    assert_eq!(! p, b.contains_key(k));
+    assert_eq!(a.get(k).or(b.get(k)), Some(v));
+    assert_eq!(v, &k.get().to_string());
+  }
+
+  let (a, b) = HashMapNZ64::<String>::new().partition_ref(|_, _| true);
+
+  assert!(a.is_empty() && b.is_empty());
+}
+
+#[test]
+fn test_for_each_slot() {
+  let mut t = HashMapNZ64::<u64>::new();
+
+  for i in 1 ..= 100 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i);
+  }
+
+  let mut u = HashMapNZ64::<u64>::new_with_mixer(t.mixer());
+  u.extend_from_map(&t);
+
+  let m = t.mixer();
+  let mut n = 0;
+
+  unsafe {
+    map::internal::for_each_slot(&mut t, |h, p| {
+      assert_eq!(m.hash(NonZeroU64::new(*p).unwrap()), h);
+      *p = *p * 3 + 1;
+      n += 1;
+    })
+  };
+
+  for (_, v) in u.iter_mut() {
+    *v = *v * 3 + 1;
+  }
+
+  assert_eq!(n, 100);
+  assert_eq!(t.iter().collect::<Vec<_>>(), u.iter().collect::<Vec<_>>());
+
+  unsafe { map::internal::for_each_slot(&mut HashMapNZ64::<u64>::new(), |_, _| panic!()) };
+}
+
+#[test]
+fn test_new_const_seeded() {
+  const SEED: [u64; 2] = [0x0123_4567_89ab_cdef, 0xfedc_ba98_7654_3211];
+
+  static TABLE: std::sync::Mutex<HashMapNZ64<u64>> = std::sync::Mutex::new(HashMapNZ64::new_const_seeded(SEED));
+
+  let mut t = TABLE.lock().unwrap();
+  let mut u = HashMapNZ64::<u64>::new_with_mixer(Mixer::new(SEED));
+
+  assert!(t.is_empty());
+  assert_eq!(t.mixer(), u.mixer());
+
+  for i in 1 ..= 100 {
+    assert_eq!(t.insert(NonZeroU64::new(i).unwrap(), i), u.insert(NonZeroU64::new(i).unwrap(), i));
+  }
+
+  for i in 1 ..= 200 {
+    let k = NonZeroU64::new(i).unwrap();
+    assert_eq!(t.get(k), u.get(k));
+  }
+
+  assert_eq!(t.iter().collect::<Vec<_>>(), u.iter().collect::<Vec<_>>());
+
+  t.reset();
+}
+
+#[test]
+fn test_sorted_entries_owned() {
+  let mut g = Rng::from_u64(1);
+  let mut t = HashMapNZ64::<String>::new();
+
+  assert!(t.sorted_entries_owned().is_empty());
+
+  for _ in 0 .. 1000 {
+    let k = g.u64() | 1;
+    let _ = t.insert(NonZeroU64::new(k).unwrap(), k.to_string());
+  }
+
+  let a = t.sorted_entries_owned();
+
+  assert_eq!(a.len(), t.len());
+  assert!(a.windows(2).all(|x| x[0].0 < x[1].0));
+  assert!(a.iter().all(|(k, v)| t.get(*k) == Some(v)));
+
+  let b = a.clone();
+
+  for (_, v) in t.iter_mut() { v.push('!'); }
+  t.clear();
+
+  assert_eq!(a, b);
+  assert!(a.iter().all(|(k, v)| *v == k.get().to_string()));
+}
+
+#[test]
+fn test_try_reserve() {
+  let mut t = HashMapNZ64::<u64>::new();
+
+  assert_eq!(t.try_reserve(usize::MAX), Err(map::TryReserveError::CapacityOverflow));
+  assert_eq!(t.try_reserve(1 << 62), Err(map::TryReserveError::CapacityOverflow));
+  assert_eq!(map::internal::num_slots(&t), 0);
+
+  assert_eq!(t.try_reserve(0), Ok(()));
+  assert_eq!(map::internal::num_slots(&t), 0);
+
+  assert_eq!(t.try_reserve(100), Ok(()));
+  let n = map::internal::num_slots(&t);
+  assert!(n >= 200);
+
+  for i in 1 ..= 100 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i);
+  }
+
+  let e = t.epoch();
+  let a = map::internal::allocation_info(&t).unwrap().0;
+
+  assert_eq!(t.try_reserve(usize::MAX - 50), Err(map::TryReserveError::CapacityOverflow));
+  assert_eq!(t.try_reserve(1 << 59), Err(map::TryReserveError::CapacityOverflow));
+
+  // A failed reservation leaves the map unchanged.
+
+  assert_eq!(t.epoch(), e);
+  assert_eq!(map::internal::allocation_info(&t).unwrap().0, a);
+  assert_eq!(t.len(), 100);
+
+  for i in 1 ..= 100 {
+    assert_eq!(t.get(NonZeroU64::new(i).unwrap()), Some(&i));
+  }
+
+  // Large values hit the limit sooner.
+
+  let mut u = HashMapNZ64::<[u8; 1 << 20]>::new();
+
+  assert_eq!(u.try_reserve(1 << 44), Err(map::TryReserveError::CapacityOverflow));
+  assert_eq!(map::internal::num_slots(&u), 0);
+
+  assert_eq!(map::TryReserveError::CapacityOverflow.to_string(), "capacity overflow");
+}
+
+#[test]
+fn test_values_mut_where() {
+  let mut t = HashMapNZ64::<u64>::new();
+
+  for i in 1 ..= 1000 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i);
+  }
+
+  let mut n = 0;
+
+  for v in t.values_mut_where(|k| (100 ..= 199).contains(&k.get())) {
+    *v = 0;
+    n += 1;
+  }
+
+  assert_eq!(n, 100);
+
+  for i in 1 ..= 1000 {
+    let x = if (100 ..= 199).contains(&i) { 0 } else { i };
+    assert_eq!(t.get(NonZeroU64::new(i).unwrap()), Some(&x));
+  }
+
+  assert_eq!(t.values_mut_where(|_| false).count(), 0);
+  assert_eq!(HashMapNZ64::<u64>::new().values_mut_where(|_| true).count(), 0);
+}
+
+#[test]
+fn test_report() {
+  let mut t = HashMapNZ64::<u64>::new();
+
+  let r = map::internal::report(&t);
+  assert_eq!(r.len, 0);
+  assert_eq!(r.capacity, 0);
+  assert_eq!(r.num_slots, 0);
+  assert!(! r.is_allocated);
+  assert_eq!(r.grow_count, 0);
+
+  for i in 1 ..= 1000 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i);
+  }
+
+  let r = map::internal::report(&t);
+  assert_eq!(r.len, t.len());
+  assert_eq!(r.num_slots, map::internal::num_slots(&t));
+  assert_eq!(r.num_bytes, map::internal::num_bytes(&t));
+  assert_eq!(r.load, map::internal::load(&t));
+  assert_eq!(r.max_probe, map::internal::max_probe_length(&t));
+  assert!(r.is_allocated);
+  assert!(r.capacity >= r.len);
+  assert!(r.mean_probe <= r.max_probe as f64);
+  assert!(r.grow_count > 0);
+
+  t.clear();
+
+  let s = map::internal::report(&t);
+  assert_eq!(s.len, 0);
+  assert_eq!(s.num_slots, r.num_slots);
+  assert_eq!(s.mean_probe, 0.);
+  assert_eq!(s.grow_count, r.grow_count);
+}
+
+#[test]
+fn test_get_many_mut() {
+  let mut t = HashMapNZ64::<u64>::new();
+  let k = |i: u64| NonZeroU64::new(i).unwrap();
+
+  for i in 1 ..= 100 {
+    let _ = t.insert(k(i), i);
+  }
+
+  let [a, b, c] = t.get_many_mut([k(1), k(50), k(100)]).unwrap();
+  core::mem::swap(a, b);
+  *c += 1;
+
+  assert_eq!(t.get(k(1)), Some(&50));
+  assert_eq!(t.get(k(50)), Some(&1));
+  assert_eq!(t.get(k(100)), Some(&101));
+
+  assert!(t.get_many_mut([k(1), k(101)]).is_none());
+  assert!(t.get_many_mut([k(1), k(2), k(1)]).is_none());
+  assert!(t.get_many_mut::<0>([]).is_some());
+  assert!(HashMapNZ64::<u64>::new().get_many_mut([k(1)]).is_none());
+
+  let [a, b] = unsafe { t.get_many_unchecked_mut([k(2), k(3)]) }.unwrap();
+  *a += *b;
+
+  assert_eq!(t.get(k(2)), Some(&5));
+}
+
+// On an x86-64 machine, looking up random keys in batches of eight took about
+// 10ns per key against 16ns for `get` with 2 ** 16 items, and 42ns against 46ns
+// with 2 ** 23 items. Out-of-order execution already overlaps some of the
+// misses of independent calls to `get`, so the win is largest for tables that
+// fit in the last-level cache but not in L1 or L2.
+
+#[test]
+fn test_get_many() {
+  let mut g = Rng::from_u64(5);
+  let mut t = HashMapNZ64::<u64>::new();
+
+  assert_eq!(t.get_many([g.nonzero_u64(), g.nonzero_u64()]), [None, None]);
+
+  for _ in 0 .. 10_000 {
+    let k = g.nonzero_u64();
+    let _ = t.insert(k, k.get());
+  }
+
+  let keys = t.keys().collect::<Vec<_>>();
+
+  for _ in 0 .. 1_000 {
+    let a = [(); 8].map(|_| if g.bool() { keys[g.u64_below(keys.len() as u64) as usize] } else { g.nonzero_u64() });
+    assert_eq!(t.get_many(a), a.map(|k| t.get(k)));
+  }
+
+  assert_eq!(t.get_many::<0>([]), []);
+}
+
+#[test]
+fn test_entry_or_insert() {
+  let m = Mixer::new([0x0123_4567_89ab_cdef, 0xfedc_ba98_7654_3211]);
+  let mut t = HashMapNZ64::<u64>::new_with_mixer(m);
+  let mut u = HashMapNZ64::<u64>::new_with_mixer(m);
+  let mut rng = Rng::from_u64(7);
+
+  for _ in 0 .. 10_000 {
+    let k = NonZeroU64::new(rng.bounded_u32(5000) as u64 + 1).unwrap();
+    let i = rng.u64();
+
+    match rng.bounded_u32(3) {
+      0 => {
+        let _ = *t.entry(k).or_insert(i);
+        if ! u.contains_key(k) { let _ = u.insert(k, i); }
+      }
+      1 => {
+        let _ = *t.entry(k).or_insert_with(|| i);
+        if ! u.contains_key(k) { let _ = u.insert(k, i); }
+      }
+      _ => {
+        let _ = t.entry(k).and_modify(|v| *v = v.wrapping_add(i));
+        if let Some(v) = u.get_mut(k) { *v = v.wrapping_add(i); }
+      }
+    }
+  }
+
+  assert_eq!(t.len(), u.len());
+  assert_eq!(map::internal::num_slots(&t), map::internal::num_slots(&u));
+
+  for (k, v) in u.iter() {
+    assert_eq!(t.get(k), Some(v));
+    assert_eq!(t.slot_index_of(k), u.slot_index_of(k));
+  }
+
+  let mut t = HashMapNZ64::<u64>::new();
+  let k = NonZeroU64::new(7).unwrap();
+
+  *t.entry(k).and_modify(|v| *v += 1).or_insert(10) += 1;
+  *t.entry(k).and_modify(|v| *v += 1).or_insert(10) += 1;
+
+  assert_eq!(t.get(k), Some(&13));
+}
+
+#[test]
+fn test_from_iter_extend() {
+  let k = |i: u64| NonZeroU64::new(i).unwrap();
+
+  let mut t = (1 ..= 1000).map(|i| (k(i), i)).collect::<HashMapNZ64<u64>>();
+
+  assert_eq!(t.len(), 1000);
+  assert_eq!(t.get(k(1)), Some(&1));
+  assert_eq!(t.get(k(500)), Some(&500));
+  assert_eq!(t.get(k(1000)), Some(&1000));
+  assert_eq!(t.get(k(1001)), None);
+
+  t.extend((501 ..= 1500).map(|i| (k(i), i + 1)));
+
+  assert_eq!(t.len(), 1500);
+  assert_eq!(t.get(k(500)), Some(&500));
+  assert_eq!(t.get(k(501)), Some(&502));
+  assert_eq!(t.get(k(1500)), Some(&1501));
+
+  let t = [(k(1), 1), (k(2), 2), (k(1), 3)].into_iter().collect::<HashMapNZ64<u64>>();
+
+  assert_eq!(t.len(), 2);
+  assert_eq!(t.get(k(1)), Some(&3));
+}
+
+#[test]
+fn test_from_array() {
+  let k = |i: u64| NonZeroU64::new(i).unwrap();
+  let t = HashMapNZ64::from([(k(1), String::from("a")), (k(2), String::from("b")), (k(1), String::from("c"))]);
+
+  assert_eq!(t.len(), 2);
+  assert_eq!(t.get(k(1)).map(|x| x.as_str()), Some("c"));
+  assert_eq!(t.get(k(2)).map(|x| x.as_str()), Some("b"));
+
+  let s = HashSetNZ64::from([k(3), k(1), k(3)]);
+
+  assert_eq!(s.len(), 2);
+  assert!(s.contains(k(1)) && s.contains(k(3)));
+  assert!(HashMapNZ64::<u64>::from([]).is_empty());
+}
+
+#[test]
+fn test_index() {
+  let k = |i: u64| NonZeroU64::new(i).unwrap();
+  let mut t = HashMapNZ64::from([(k(1), 10), (k(2), 20)]);
+
+  t[k(2)] += 1;
+
+  assert_eq!(t[k(1)], 10);
+  assert_eq!(t[k(2)], 21);
+}
+
+#[test]
+#[should_panic(expected = "no entry found for key 3")]
+fn test_index_missing() {
+  let k = |i: u64| NonZeroU64::new(i).unwrap();
+  let t = HashMapNZ64::from([(k(1), 10), (k(2), 20)]);
+  let _ = t[k(3)];
+}
+
+#[test]
+fn test_iter_unordered() {
+  let mut t = HashMapNZ64::<u64>::new();
+
+  assert_eq!(t.iter().len(), 0);
+  assert!(t.iter_mut().next().is_none());
+
+  for i in 1 ..= 1000 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i);
+  }
+
+  let mut it = t.iter();
+  assert_eq!(it.len(), 1000);
+
+  let mut a = Vec::new();
+  while let Some((k, &v)) = it.next() {
+    assert_eq!(k.get(), v);
+    assert_eq!(it.len(), 1000 - a.len() - 1);
+    a.push(k.get());
+  }
+
+  assert!(it.next().is_none());
+  assert!(it.next().is_none());
+
+  a.sort();
+  assert_eq!(a, (1 ..= 1000).collect::<Vec<_>>());
+
+  let mut it = t.iter_mut();
+  assert_eq!(it.len(), 1000);
+  for (k, v) in &mut it { *v += k.get(); }
+  assert!(it.next().is_none());
+
+  assert!(t.iter().all(|(k, &v)| v == 2 * k.get()));
+}
+
+#[test]
+fn test_values_sum() {
+  let mut t = HashMapNZ64::<u64>::new();
+
+  assert_eq!(t.values().sum::<u64>(), 0);
+
+  for i in 1 ..= 1000 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i);
+  }
+
+  assert_eq!(t.values().len(), 1000);
+  assert_eq!(t.values().sum::<u64>(), 500_500);
+
+  for v in t.values_mut() { *v *= 2; }
+
+  assert_eq!(t.values_mut().len(), 1000);
+  assert_eq!(t.values().sum::<u64>(), 1_001_000);
+}
+
+#[test]
+fn test_keys() {
+  let mut t = HashMapNZ64::<u64>::new();
+
+  for i in 1 ..= 1000 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i);
+  }
+
+  let mut it = t.keys();
+  assert_eq!(it.len(), 1000);
+
+  for _ in 0 .. 400 { let _ = it.next(); }
+
+  let u = it.clone();
+  assert_eq!(u.len(), 600);
+  assert_eq!(it.collect::<Vec<_>>(), u.collect::<Vec<_>>());
+
+  let mut a = t.keys().map(|k| k.get()).collect::<Vec<_>>();
+  a.sort();
+  assert_eq!(a, (1 ..= 1000).collect::<Vec<_>>());
+  assert_eq!(t.keys().zip(t.iter()).filter(|(k, (j, _))| k != j).count(), 0);
+}
+
+#[test]
+fn test_retain() {
+  let mut t = HashMapNZ64::<u64>::new();
+  let mut u = HashMapNZ64::<u64>::new_with_mixer(t.mixer());
+
+  for i in 1 ..= 200 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i);
+  }
+
+  let mut n = 0;
+
+  t.retain(|k, v| {
+    assert_eq!(k.get(), *v);
+    n += 1;
+    *v += 1;
+    *v % 2 == 1
+  });
+
+  assert_eq!(n, 200);
+  assert_eq!(t.len(), 100);
+
+  for i in (2 ..= 200).step_by(2) {
+    let _ = u.insert(NonZeroU64::new(i).unwrap(), i + 1);
+  }
+
+  for i in 1 ..= 200 {
+    let k = NonZeroU64::new(i).unwrap();
+    assert_eq!(t.get(k), u.get(k));
+  }
+
+  t.retain(|_, _| false);
+  assert!(t.is_empty());
+
+  let _ = t.insert(NonZeroU64::new(1).unwrap(), 1);
+  assert_eq!(t.len(), 1);
+}
+
+#[test]
+fn test_drain() {
+  use std::rc::Rc;
+
+  let n = Rc::new(());
+  let mut t = HashMapNZ64::new();
+
+  for i in 1 ..= 100 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), (i, n.clone()));
+  }
+
+  let before = map::internal::allocation_info(&t);
+  let mut a = t.drain().map(|(k, v)| { assert_eq!(k.get(), v.0); v.0 }).collect::<Vec<_>>();
+  a.sort();
+
+  assert_eq!(a, (1 ..= 100).collect::<Vec<_>>());
+  assert_eq!(Rc::strong_count(&n), 1);
+  assert!(t.is_empty());
+  assert_eq!(map::internal::allocation_info(&t), before);
+
+  for i in 1 ..= 100 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), (i, n.clone()));
+  }
+
+  let mut it = t.drain();
+  assert_eq!(it.len(), 100);
+  let _ = it.next();
+  let _ = it.next();
+  assert_eq!(it.len(), 98);
+  drop(it);
+
+  assert_eq!(Rc::strong_count(&n), 1);
+  assert!(t.is_empty());
+
+  for i in 1 ..= 100 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), (i, n.clone()));
+  }
+
+  let mut it = t.drain();
+  for _ in 0 .. 10 { let _ = it.next(); }
+  core::mem::forget(it);
+
+  assert_eq!(t.len(), 90);
+  assert_eq!(Rc::strong_count(&n), 91);
+  assert_eq!((1 ..= 100).filter(|&i| t.contains_key(NonZeroU64::new(i).unwrap())).count(), 90);
+  assert_eq!(t.drain().count(), 90);
+  assert_eq!(Rc::strong_count(&n), 1);
+}
+
+#[test]
+fn test_reserve() {
+  let mut t = HashMapNZ64::<u64>::new_with_mixer(Mixer::new([1, 2]));
+
+  t.reserve(1000);
+
+  let n = map::internal::num_bytes(&t);
+  assert!(n > 0);
+
+  for i in 1 ..= 1000 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i);
+    assert_eq!(map::internal::num_bytes(&t), n);
+  }
+
+  t.reserve(0);
+  assert_eq!(map::internal::num_bytes(&t), n);
+
+  let mut u = HashMapNZ64::<u64>::with_capacity(1000);
+  assert_eq!(map::internal::num_bytes(&u), n);
+  assert!(u.is_empty());
+
+  for i in 1 ..= 1000 {
+    let _ = u.insert(NonZeroU64::new(i).unwrap(), i);
+  }
+
+  assert_eq!(u.len(), 1000);
+  assert!(HashMapNZ64::<u64>::with_capacity(0).is_empty());
+}
+
+#[test]
+fn test_clone_layout() {
+  let mut g = Rng::from_u64(3);
+  let mut t = HashMapNZ64::<u64>::new();
+
+  for _ in 0 .. 10_000 {
+    let k = NonZeroU64::new(g.bounded_u32(5000) as u64 + 1).unwrap();
+    if g.bool() { let _ = t.insert(k, g.u64()); } else { let _ = t.remove(k); }
+  }
+
+  let u = t.clone();
+
+  assert_eq!(u.len(), t.len());
+  assert_eq!(u.mixer(), t.mixer());
+
+  for i in 1 ..= 6000 {
+    let k = NonZeroU64::new(i).unwrap();
+    assert_eq!(u.get(k), t.get(k));
+    assert_eq!(u.slot_index_of(k), t.slot_index_of(k));
+  }
+}
+
+#[test]
+fn test_eq() {
+  let mut t = HashMapNZ64::<u64>::new();
+  let mut u = HashMapNZ64::<u64>::new();
+
+  assert_eq!(t, u);
+
+  for i in 1 ..= 100 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i);
+  }
+
+  for i in (1 ..= 100).rev() {
+    let _ = u.insert(NonZeroU64::new(i).unwrap(), i);
+  }
+
+  assert_ne!(t.mixer(), u.mixer());
+  assert_eq!(t, u);
+
+  let _ = u.insert(NonZeroU64::new(50).unwrap(), 0);
+  assert_ne!(t, u);
+
+  let _ = u.insert(NonZeroU64::new(50).unwrap(), 50);
+  let _ = u.insert(NonZeroU64::new(101).unwrap(), 101);
+  assert_ne!(t, u);
+  assert_ne!(u, t);
+}
+
+#[test]
+fn test_into_iter_drop() {
+  use std::cell::Cell;
+
+  struct Counted<'a>(u64, &'a Cell<usize>);
+
+  impl<'a> Drop for Counted<'a> {
+    fn drop(&mut self) { self.1.set(self.1.get() + 1); }
+  }
+
+  let n = Cell::new(0);
+  let mut t = HashMapNZ64::new();
+
+  for i in 1 ..= 100 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), Counted(i, &n));
+  }
+
+  let mut a = t.into_iter().map(|(k, v)| { assert_eq!(k.get(), v.0); k.get() }).collect::<Vec<_>>();
+  a.sort();
+
+  assert_eq!(a, (1 ..= 100).collect::<Vec<_>>());
+  assert_eq!(n.get(), 100);
+
+  let n = Cell::new(0);
+  let mut t = HashMapNZ64::new();
+
+  for i in 1 ..= 100 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), Counted(i, &n));
+  }
+
+  let mut it = t.into_iter();
+  let a = (0 .. 30).map(|_| it.next().unwrap()).collect::<Vec<_>>();
+
+  assert_eq!(n.get(), 0);
+  drop(it);
+  assert_eq!(n.get(), 70);
+  drop(a);
+  assert_eq!(n.get(), 100);
+
+  assert_eq!(HashMapNZ64::<Counted<'_>>::new().into_iter().count(), 0);
+}
+
+#[test]
+fn test_get_key_value_and_remove_entry() {
+  let k = |i: u64| NonZeroU64::new(i).unwrap();
+  let mut t = HashMapNZ64::from([(k(1), 10), (k(2), 20)]);
+
+  assert_eq!(t.get_key_value(k(1)), Some((k(1), &10)));
+  assert_eq!(t.get_key_value(k(3)), None);
+  assert_eq!(t.remove_entry(k(2)), Some((k(2), 20)));
+  assert_eq!(t.remove_entry(k(2)), None);
+  assert_eq!(t.len(), 1);
+  assert_eq!(HashMapNZ64::<u64>::new().remove_entry(k(1)), None);
+}
+
+#[test]
+fn test_get_or_insert_with() {
+  let mut t = HashMapNZ64::<u64>::new();
+  let mut g = Rng::from_u64(5);
+  let mut a = vec![0; 1000];
+
+  for _ in 0 .. 10_000 {
+    let i = g.bounded_u32(999) as usize;
+    *t.get_or_insert_with(NonZeroU64::new(i as u64 + 1).unwrap(), || 0) += 1;
+    a[i] += 1;
+  }
+
+  for (i, &n) in a.iter().enumerate() {
+    let k = NonZeroU64::new(i as u64 + 1).unwrap();
+    assert_eq!(t.get(k).copied().unwrap_or(0), n);
+  }
+
+  assert_eq!(t.len(), a.iter().filter(|&&n| n != 0).count());
+}
+
+// While set, makes `TestAlloc` fail every allocation on the current thread.
+
+std::thread_local! {
+  static FAIL_ALLOC: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+fn fail_alloc() -> bool {
+  FAIL_ALLOC.try_with(|x| x.get()).unwrap_or(false)
+}
+
+#[test]
+fn test_try_insert() {
+  let k = |i: u64| NonZeroU64::new(i).unwrap();
+  let mut t = HashMapNZ64::<String>::new();
+
+  FAIL_ALLOC.with(|x| x.set(true));
+  let r = t.try_insert(k(1), String::new());
+  FAIL_ALLOC.with(|x| x.set(false));
+
+  let e = r.unwrap_err();
+  assert!(matches!(e.error, map::TryReserveError::AllocError { .. }));
+  assert_eq!(e.to_string(), "memory allocation failed");
+  assert!(t.is_empty());
+  assert_eq!(map::internal::num_slots(&t), 0);
+
+  assert_eq!(t.try_insert(k(1), "a".to_string()).unwrap(), None);
+  assert_eq!(t.try_insert(k(1), "b".to_string()).unwrap(), Some("a".to_string()));
+
+  // Fill the table until the next insertion of a new key would grow it, and
+  // check that a failed growth leaves the map unchanged.
+
+  let mut i = 2;
+
+  loop {
+    let n = map::internal::num_slots(&t);
+    let v = i.to_string();
+
+    FAIL_ALLOC.with(|x| x.set(true));
+    let r = t.try_insert(k(i), v);
+    FAIL_ALLOC.with(|x| x.set(false));
+
+    match r {
+      Ok(x) => {
+        assert_eq!(x, None);
+        assert_eq!(map::internal::num_slots(&t), n);
+        i += 1;
+      }
+      Err(e) => {
+        assert_eq!(e.value, i.to_string());
+        assert_eq!(map::internal::num_slots(&t), n);
+        break;
+      }
+    }
+  }
+
+  assert_eq!(t.len() as u64, i - 1);
+  assert!(! t.contains_key(k(i)));
+
+  for j in 2 .. i {
+    assert_eq!(t.get(k(j)), Some(&j.to_string()));
+  }
+
+  // Replacing the value of a present key never allocates.
+
+  let v = "c".to_string();
+
+  FAIL_ALLOC.with(|x| x.set(true));
+  let r = t.try_insert(k(1), v);
+  FAIL_ALLOC.with(|x| x.set(false));
+
+  assert_eq!(r.unwrap(), Some("b".to_string()));
+  assert_eq!(t.try_insert(k(i), i.to_string()).unwrap(), None);
+  assert_eq!(t.len() as u64, i);
+}
+
+#[test]
+fn test_probe_length_statistics() {
+  let mut t = HashMapNZ64::<u64>::new();
+
+  assert_eq!(map::internal::probe_length_average(&t), 0.);
+  assert_eq!(&*map::internal::probe_length_histogram(&t), &[0]);
+
+  for i in 1 ..= 10_000 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i);
+  }
+
+  let a = map::internal::probe_length_histogram(&t);
+  let m = map::internal::probe_length_average(&t);
+
+  assert_eq!(a.iter().sum::<usize>(), 10_000);
+  assert_eq!(a.len(), map::internal::max_probe_length(&t) + 1);
+  assert_ne!(a[a.len() - 1], 0);
+  assert_eq!(m, a.iter().enumerate().map(|(i, &n)| i * n).sum::<usize>() as f64 / 10_000.);
+  assert!(m < 2.);
+}
+
+#[test]
+fn test_clear_panicking_drop() {
+  use std::panic::AssertUnwindSafe;
+
+  struct Bomb(u64);
+
+  impl Drop for Bomb {
+    fn drop(&mut self) {
+      if self.0 == 50 { panic!("boom") }
+    }
+  }
+
+  let mut t = HashMapNZ64::new();
+
+  for i in 1 ..= 100 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), Bomb(i));
+  }
+
+  let slots = map::internal::num_slots(&t);
+  let r = std::panic::catch_unwind(AssertUnwindSafe(|| t.clear()));
+
+  assert!(r.is_err());
+  assert!(t.is_empty());
+  assert_eq!(t.iter().count(), 0);
+  assert_eq!(map::internal::num_slots(&t), slots);
+
+  for i in 1 ..= 100 {
+    assert!(! t.contains_key(NonZeroU64::new(i).unwrap()));
+  }
+
+  for i in 101 ..= 200 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), Bomb(i));
+  }
+
+  assert_eq!(t.len(), 100);
+  assert_eq!(map::internal::num_slots(&t), slots);
+}
+
+#[test]
+fn test_shrink_to_fit() {
+  let mut t = HashMapNZ64::<u64>::new_with_mixer(Mixer::new([3, 4]));
+
+  t.shrink_to_fit();
+  assert_eq!(map::internal::num_bytes(&t), 0);
+
+  for i in 1 ..= 1000 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i);
+  }
+
+  // A table that is already minimal is left alone.
+
+  let a = map::internal::allocation_info(&t);
+  t.shrink_to_fit();
+  assert_eq!(map::internal::allocation_info(&t), a);
+
+  for i in 1 ..= 900 {
+    let _ = t.remove(NonZeroU64::new(i).unwrap());
+  }
+
+  let m = t.mixer();
+  let x = map::internal::num_bytes(&t);
+
+  t.shrink_to_fit();
+
+  assert!(map::internal::num_bytes(&t) < x);
+  assert_eq!(t.mixer(), m);
+  assert_eq!(t.len(), 100);
+
+  for i in 1 ..= 1000 {
+    let k = NonZeroU64::new(i).unwrap();
+    assert_eq!(t.get(k), if i > 900 { Some(&i) } else { None });
+  }
+
+  t.clear();
+  let a = map::internal::allocation_info(&t);
+  t.shrink_to_fit();
+  assert_eq!(map::internal::allocation_info(&t), a);
+}
+
+#[test]
+fn test_sorted_keys_into() {
+  let mut g = Rng::from_u64(9);
+  let mut t = HashMapNZ64::<u64>::new();
+  let mut a = Vec::new();
+
+  t.sorted_keys_into(&mut a);
+  assert!(a.is_empty());
+
+  for n in [100, 10, 1000, 0] {
+    t.clear();
+
+    for _ in 0 .. n {
+      let k = NonZeroU64::new(g.u64() | 1).unwrap();
+      let _ = t.insert(k, k.get() ^ 1);
+    }
+
+    let mut b = vec![(NonZeroU64::MIN, &0)];
+
+    t.sorted_keys_into(&mut a);
+    t.items_sorted_by_key_into(&mut b);
+    t.items_sorted_by_key_into(&mut b);
+
+    assert_eq!(a.len(), t.len());
+    assert!(a.windows(2).all(|x| x[0] < x[1]));
+    assert!(a.iter().all(|&k| t.contains_key(k)));
+    assert_eq!(&*t.sorted_keys(), &a[..]);
+    assert_eq!(b.iter().map(|x| x.0).collect::<Vec<_>>(), a);
+    assert!(b.iter().all(|&(k, &v)| v == k.get() ^ 1));
+    assert_eq!(&*t.items_sorted_by_key(), &b[..]);
+  }
+}
+
+// An allocator that counts the allocations and deallocations it serves.
+
+#[derive(Default)]
+struct CountingAlloc {
+  allocs: std::cell::Cell<usize>,
+  deallocs: std::cell::Cell<usize>,
+}
+
+unsafe impl wordmap::allocator::Allocator for CountingAlloc {
+  fn allocate(&self, layout: std::alloc::Layout) -> Result<std::ptr::NonNull<[u8]>, wordmap::allocator::AllocError> {
+    self.allocs.set(self.allocs.get() + 1);
+    wordmap::allocator::Global.allocate(layout)
+  }
+
+  unsafe fn deallocate(&self, ptr: std::ptr::NonNull<u8>, layout: std::alloc::Layout) {
+    self.deallocs.set(self.deallocs.get() + 1);
+    unsafe { wordmap::allocator::Global.deallocate(ptr, layout) }
+  }
+}
+
+#[test]
+fn test_new_in() {
+  let a = CountingAlloc::default();
+
+  {
+    let mut t = HashMapNZ64::new_in(&a);
+    assert_eq!(a.allocs.get(), 0);
+
+    for i in 1 ..= 1000 {
+      let _ = t.insert(NonZeroU64::new(i).unwrap(), i.to_string());
+    }
+
+    assert!(a.allocs.get() > 1);
+    assert_eq!(a.allocs.get(), a.deallocs.get() + 1);
+
+    let u = t.clone();
+    assert_eq!(u, t);
+    assert_eq!(a.allocs.get(), a.deallocs.get() + 2);
+
+    t.reset();
+    assert_eq!(a.allocs.get(), a.deallocs.get() + 1);
+
+    let _ = t.insert(NonZeroU64::new(1).unwrap(), String::new());
+    assert_eq!(u.into_iter().take(10).count(), 10);
+    assert_eq!(a.allocs.get(), a.deallocs.get() + 1);
+  }
+
+  assert_eq!(a.allocs.get(), a.deallocs.get());
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_par_iter() {
+  use rayon::iter::ParallelIterator;
+
+  let t = HashMapNZ64::<u64>::new();
+  assert_eq!(t.par_iter().count(), 0);
+  assert_eq!(t.par_values().sum::<u64>(), 0);
+
+  let mut g = Rng::from_u64(17);
+  let mut t = HashMapNZ64::new_with_mixer(Mixer::new([5, 6]));
+
+  for _ in 0 .. 100_000 {
+    let _ = t.insert(NonZeroU64::new(g.u64() | 1).unwrap(), g.u64() >> 32);
+  }
+
+  let serial = t.values().sum::<u64>();
+  let keys = t.keys().fold(0u64, |a, k| a ^ k.get());
+
+  assert_eq!(t.par_values().sum::<u64>(), serial);
+  assert_eq!(t.par_iter().map(|(_, &v)| v).sum::<u64>(), serial);
+  assert_eq!(t.par_iter().map(|(k, _)| k.get()).reduce(|| 0, |a, b| a ^ b), keys);
+  assert_eq!(t.par_iter().count(), t.len());
+  assert!(t.par_iter().all(|(k, &v)| t.get(k) == Some(&v)));
+
+  let mut a = t.par_iter().map(|(k, &v)| (k, v)).collect::<Vec<_>>();
+  let mut b = t.iter().map(|(k, &v)| (k, v)).collect::<Vec<_>>();
+  a.sort();
+  b.sort();
+  assert_eq!(a, b);
+  assert_eq!(t.par_values().collect::<Vec<_>>().len(), t.len());
+}
+
+#[test]
+fn test_capacity() {
+  let k = |i: u64| NonZeroU64::new(i).unwrap();
+  let mut t = HashMapNZ64::<u64>::new_with_mixer(Mixer::new([7, 8]));
+
+  assert_eq!(t.capacity(), 0);
+
+  let _ = t.insert(k(1), 1);
+  let c = t.capacity();
+  let n = map::internal::num_slots(&t);
+  assert!(c > 1 && c < n);
+
+  for i in 2 ..= c as u64 {
+    let _ = t.insert(k(i), i);
+    assert_eq!(t.capacity(), c);
+  }
+
+  assert_eq!(map::internal::num_slots(&t), n);
+
+  let _ = t.insert(k(c as u64 + 1), 0);
+  assert!(map::internal::num_slots(&t) > n);
+  assert!(t.capacity() > c);
+
+  // Replacing a value or removing a key doesn't change the capacity.
+
+  let c = t.capacity();
+  let _ = t.insert(k(1), 2);
+  assert_eq!(t.capacity(), c);
+  let _ = t.remove(k(1));
+  assert_eq!(t.capacity(), c);
+
+  let mut t = HashMapNZ64::<u64>::new_with_mixer(Mixer::new([7, 8]));
+  t.reserve(1000);
+  let c = t.capacity();
+  assert!(c >= 1000);
+
+  for i in 1 ..= c as u64 {
+    let _ = t.insert(k(i), i);
+  }
+
+  assert_eq!(map::internal::report(&t).grow_count, 0);
+
+  let _ = t.insert(k(c as u64 + 1), 0);
+  assert_eq!(map::internal::report(&t).grow_count, 1);
+}
+
+#[test]
+fn test_default() {
+  #[derive(Default)]
+  struct Index {
+    map: HashMapNZ64<String>,
+    set: HashSetNZ64,
+  }
+
+  let mut x = Index::default();
+  assert!(x.map.is_empty());
+  assert!(x.set.is_empty());
+
+  let _ = x.map.insert(NonZeroU64::new(1).unwrap(), String::new());
+  assert_eq!(x.map.len(), 1);
+
+  let y = Index { set: HashSetNZ64::from([NonZeroU64::MIN]), ..Default::default() };
+  assert!(y.map.is_empty());
+  assert_eq!(y.set.len(), 1);
+}
+
+#[test]
+fn test_append() {
+  let k = |i: u64| NonZeroU64::new(i).unwrap();
+  let mut t = HashMapNZ64::new();
+  let mut u = HashMapNZ64::new();
+
+  for i in 1 ..= 100 {
+    let _ = t.insert(k(i), i);
+  }
+
+  for i in 51 ..= 200 {
+    let _ = u.insert(k(i), 1000 + i);
+  }
+
+  let slots = map::internal::num_slots(&u);
+
+  t.append(&mut u);
+
+  assert_eq!(t.len(), 200);
+  assert!(u.is_empty());
+  assert_eq!(map::internal::num_slots(&u), slots);
+
+  for i in 1 ..= 200 {
+    assert_eq!(t[k(i)], if i <= 50 { i } else { 1000 + i });
+  }
+
+  t.append(&mut u);
+  assert_eq!(t.len(), 200);
+
+  let _ = u.insert(k(1), 7);
+  assert_eq!(u.len(), 1);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_std_hash_map() {
+  use std::collections::HashMap;
+
+  let mut g = Rng::from_u64(11);
+  let mut t = HashMapNZ64::new();
+
+  for _ in 0 .. 1000 {
+    let _ = t.insert(g.nonzero_u64(), g.u64().to_string());
+  }
+
+  let u = HashMap::<NonZeroU64, String>::from(t.clone());
+  assert_eq!(u.len(), t.len());
+
+  for (k, v) in t.iter() {
+    assert_eq!(u.get(&k), Some(v));
+  }
+
+  let v = HashMapNZ64::from(u);
+  assert_eq!(v, t);
+
+  let e = HashMap::<NonZeroU64, u64>::from(HashMapNZ64::new());
+  assert!(e.is_empty());
+  assert!(HashMapNZ64::from(e).is_empty());
+}
+
+#[test]
+fn test_autoshrink() {
+  let k = |i: u64| NonZeroU64::new(i).unwrap();
+  let mut t = HashMapNZ64::new_with_autoshrink();
+  let mut u = HashMapNZ64::new();
+
+  for i in 1 ..= 100_000 {
+    let _ = t.insert(k(i), i);
+    let _ = u.insert(k(i), i);
+  }
+
+  let bytes = map::internal::num_bytes(&t);
+  assert_eq!(map::internal::num_bytes(&u), bytes);
+
+  for i in 1 ..= 95_000 {
+    assert_eq!(t.remove(k(i)), Some(i));
+    assert_eq!(u.remove(k(i)), Some(i));
+  }
+
+  assert_eq!(map::internal::num_bytes(&u), bytes);
+  assert!(map::internal::num_bytes(&t) <= bytes / 8);
+  assert!(map::internal::load(&t) >= 1. / 8.);
+  assert_eq!(t, u);
+
+  // A clone also shrinks, and removing every item shrinks to the initial
+  // table.
+
+  let mut v = t.clone();
+
+  for i in 95_001 ..= 100_000 {
+    assert_eq!(v.remove(k(i)), Some(i));
+  }
+
+  assert!(v.is_empty());
+  assert_eq!(map::internal::num_bytes(&v), map::internal::num_bytes(&HashMapNZ64::<u64>::from([(k(1), 1)])));
+
+  let _ = v.insert(k(1), 1);
+  assert_eq!(v[k(1)], 1);
+
+  // Pinning the capacity only covers insertions, and a removal may still move
+  // the table.
+
+  let mut w = HashMapNZ64::new_with_autoshrink();
+
+  for i in 1 ..= 10_000 {
+    let _ = w.insert(k(i), i);
+  }
+
+  for i in 1 ..= 9_000 {
+    let _ = w.remove(k(i));
+  }
+
+  w.reserve_for_transaction(8);
+  assert!(w.pin_capacity(8));
+
+  let a = map::internal::allocation_info(&w).unwrap().0;
+  let mut moved = false;
+
+  for i in 9_001 ..= 9_950 {
+    let _ = w.remove(k(i));
+    moved |= map::internal::allocation_info(&w).unwrap().0 != a;
+  }
+
+  assert!(moved);
+}
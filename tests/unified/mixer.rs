@@ -0,0 +1,102 @@
+use crate::prelude::*;
+
+#[test]
+fn test_unhash() {
+  let mut g = Rng::from_u64(1);
+
+  for _ in 0 .. 10 {
+    let m = Mixer::new_seeded(&mut g);
+    let n = Mixer::new(m.seed());
+
+    for _ in 0 .. 1000 {
+      let k = NonZeroU64::new(g.u64() | 1).unwrap();
+
+      assert_eq!(m.unhash(m.hash(k)), k);
+      assert_eq!(n.unhash(m.hash(k)), k);
+    }
+  }
+
+  let m = Mixer::new([0x1234_5678_9abc_def1, 0x0fed_cba9_8765_4321]);
+
+  for i in 1 ..= 1000 {
+    let k = NonZeroU64::new(i).unwrap();
+
+    assert_eq!(m.unhash(m.hash(k)), k);
+    assert_eq!(m.hash(m.unhash(k)), k);
+  }
+}
+
+#[test]
+fn test_no_swap() {
+  let mut g = Rng::from_u64(2);
+
+  for _ in 0 .. 10 {
+    let seed = [g.u64(), g.u64()];
+    let m = Mixer::new_no_swap(seed);
+    let n = Mixer::new(seed);
+    let r = m.invert();
+
+    assert!(! m.swap_bytes());
+    assert!(! r.swap_bytes());
+    assert!(n.swap_bytes());
+    assert_eq!(m.seed(), n.seed());
+    assert_ne!(m, n);
+    assert_eq!(r.invert(), m);
+    assert_eq!(Mixer::new_no_swap(m.seed()), m);
+
+    for _ in 0 .. 1000 {
+      let k = NonZeroU64::new(g.u64() | 1).unwrap();
+
+      assert_eq!(r.hash(m.hash(k)), k);
+      assert_eq!(m.hash(r.hash(k)), k);
+      assert_eq!(m.unhash(m.hash(k)), k);
+      assert_eq!(m.hash(k).get(), k.get().wrapping_mul(seed[0] | 1).wrapping_mul(seed[1] | 1));
+    }
+  }
+
+  let mut t = HashMapNZ64::<u64>::new_with_mixer(Mixer::new_no_swap([3, 5]));
+
+  for i in 1 ..= 1000 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i);
+  }
+
+  for i in 1 ..= 1000 {
+    assert_eq!(t.get(NonZeroU64::new(i).unwrap()), Some(&i));
+  }
+
+  // The seed of a seeded mixer has inverse words, so without the swap the
+  // hash is the identity.
+
+  let m = Mixer::new_no_swap(Mixer::new_seeded(&mut g).seed());
+
+  for _ in 0 .. 1000 {
+    let k = NonZeroU64::new(g.u64() | 1).unwrap();
+    assert_eq!(m.hash(k), k);
+  }
+}
+
+#[test]
+fn test_invert_round_trip() {
+  let mut g = Rng::from_u64(2);
+
+  for i in 0 .. 100 {
+    let s = [g.u64(), g.u64()];
+    let m = if i % 2 == 0 { Mixer::new(s) } else { Mixer::new_no_swap(s) };
+
+    assert_eq!(m.invert().invert(), m);
+    assert_eq!(m.invert().swap_bytes(), m.swap_bytes());
+
+    for _ in 0 .. 1000 {
+      let x = NonZeroU64::new(g.u64() | 1 << 63).unwrap();
+
+      assert_eq!(m.invert().hash(m.hash(x)), x);
+      assert_eq!(m.hash(m.invert().hash(x)), x);
+    }
+  }
+
+  let m = Mixer::new_seeded(&mut g);
+
+  assert_eq!(m.invert().invert(), m);
+  assert_eq!(m.invert().hash(m.hash(NonZeroU64::MAX)), NonZeroU64::MAX);
+  assert_eq!(m.invert().hash(m.hash(NonZeroU64::MIN)), NonZeroU64::MIN);
+}
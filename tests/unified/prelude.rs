@@ -1,6 +1,14 @@
 pub(crate) use expect_test::expect;
+pub(crate) use wordmap::intern::InternerNZ64;
 pub(crate) use wordmap::map::HashMapNZ64;
 pub(crate) use wordmap::map;
+pub(crate) use wordmap::mixer::Mixer;
+pub(crate) use wordmap::ptr::Ptr;
+pub(crate) use wordmap::rng::Rng;
+pub(crate) use wordmap::set::HashSetNZ64;
+pub(crate) use wordmap::soa::SoaMapNZ64;
+pub(crate) use wordmap::soa;
 pub(crate) use std::fmt::Write;
+pub(crate) use std::num::NonZeroU128;
 pub(crate) use std::num::NonZeroU64;
 pub(crate) use std::writeln;
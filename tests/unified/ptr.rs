@@ -0,0 +1,124 @@
+use crate::prelude::*;
+
+// NB: These tests are meant to also pass under Miri with strict provenance,
+//
+//   MIRIFLAGS=-Zmiri-strict-provenance cargo +nightly miri test ptr
+//
+// so they only dereference pointers that keep the provenance of a real
+// allocation.
+
+#[test]
+fn test_addr_and_provenance() {
+  let a = [1u64, 2, 3, 4];
+  let p = Ptr::from_ref(&a);
+
+  assert_eq!(p.addr(), a.as_ptr() as usize);
+  assert_eq!(Ptr::invalid(12345).addr(), 12345);
+  assert!(Ptr::NULL.is_null());
+  assert!(! p.is_null());
+
+  let q = p.with_addr(p.addr() + 16);
+
+  assert_eq!(q, p.gep::<u64>(2));
+  assert_eq!(unsafe { q.read::<u64>() }, 3);
+
+  let r = q.map_addr(|x| x - 8);
+
+  assert_eq!(r - p, 8);
+  assert_eq!(unsafe { r.read::<u64>() }, 2);
+  assert_eq!(p.map_addr(|x| x), p);
+}
+
+#[test]
+fn test_alignment() {
+  for (addr, align, ok) in [(0, 1, true), (0, 4096, true), (8, 8, true), (8, 16, false), (12, 4, true), (13, 2, false), (1 << 20, 1 << 12, true)] {
+    assert_eq!(Ptr::invalid(addr).is_aligned_to(align), ok);
+  }
+
+  #[repr(align(64))]
+  struct Aligned(#[allow(dead_code)] [u8; 128]);
+
+  let a = Aligned([0; 128]);
+  let p = Ptr::from_ref(&a);
+
+  assert!(p.is_aligned::<Aligned>());
+  assert!(p.is_aligned::<u64>());
+  assert!(p.is_aligned_to(64));
+  assert_eq!(p.align_offset(64), 0);
+
+  let q = p.add(1);
+
+  assert!(! q.is_aligned::<u16>());
+  assert!(q.is_aligned::<u8>());
+  assert_eq!(q.align_offset(8), 7);
+  assert_eq!(q.add(q.align_offset(16) as isize).addr() % 16, 0);
+}
+
+#[test]
+fn test_offset_from_count() {
+  #[repr(C)]
+  struct Slot<T> {
+    hash: u64,
+    data: T,
+  }
+
+  let a = (0 .. 8).map(|i| Slot { hash: i, data: 10 * i }).collect::<Vec<Slot<u64>>>();
+  let p = Ptr::from_ref(&a[0]);
+
+  for (i, x) in a.iter().enumerate() {
+    let q = Ptr::from_ref(x);
+    assert_eq!(q.offset_from_count::<Slot<u64>>(p), i as isize);
+    assert_eq!(p.offset_from_count::<Slot<u64>>(q), - (i as isize));
+    assert_eq!(q.offset_from_count::<u64>(p), 2 * i as isize);
+    assert_eq!(unsafe { q.read::<u64>() }, x.hash);
+    assert_eq!(unsafe { q.gep::<u64>(1).read::<u64>() }, x.data);
+  }
+
+  let b = Ptr::from_ref(&a[7]).gep::<Slot<u64>>(1);
+  assert_eq!(b.offset_from_count::<Slot<u64>>(p), 8);
+}
+
+#[test]
+#[should_panic(expected = "must not be zero-sized")]
+fn test_offset_from_count_zero_sized() {
+  let a = [0u64; 2];
+  let p = Ptr::from_ref(&a);
+  let _ = p.offset_from_count::<()>(p);
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "isn't a multiple of the size")]
+fn test_offset_from_count_inexact() {
+  let a = [0u64; 2];
+  let p = Ptr::from_ref(&a);
+  let _ = p.add(4).offset_from_count::<u64>(p);
+}
+
+#[test]
+fn test_read_at_and_write_at() {
+  let mut a = [10u32, 20, 30, 40, 50];
+  let p = Ptr::from_mut_ref(&mut a);
+  let m = p.gep::<u32>(2);
+
+  assert_eq!(unsafe { p.read_at::<u32>(0) }, 10);
+  assert_eq!(unsafe { m.read_at::<u32>(0) }, 30);
+  assert_eq!(unsafe { m.read_at::<u32>(-2) }, 10);
+  assert_eq!(unsafe { m.read_at::<u32>(2) }, 50);
+
+  unsafe { m.write_at::<u32>(-1, 21) };
+  unsafe { m.write_at::<u32>(1, 41) };
+  unsafe { p.write_at::<u32>(4, 51) };
+
+  assert_eq!(a, [10, 21, 30, 41, 51]);
+}
+
+#[test]
+fn test_display() {
+  let p = Ptr::invalid(0xab);
+  let s = format!("{}", p);
+
+  assert_eq!(s, format!("{:?}", p));
+  assert_eq!(s.len(), 2 + (usize::BITS / 4) as usize);
+  assert!(s.starts_with("0x") && s.ends_with("ab"));
+}
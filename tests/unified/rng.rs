@@ -0,0 +1,263 @@
+use crate::prelude::*;
+
+#[test]
+fn test_for_label() {
+  let base = NonZeroU128::new(0x1234_5678_9abc_def0).unwrap();
+
+  let stream = |label: &[u8]| {
+    let mut g = Rng::for_label(base, label);
+    (0 .. 8).map(|_| g.u64()).collect::<Vec<_>>()
+  };
+
+  assert_eq!(stream(b"experiment_x"), stream(b"experiment_x"));
+  assert_ne!(stream(b"experiment_x"), stream(b"experiment_y"));
+  assert_ne!(stream(b""), stream(b"\0"));
+  assert_ne!(stream(b"a"), stream(b"a\0"));
+
+  let x = stream(b"experiment_x");
+  let y = stream(b"experiment_y");
+  let d = x.iter().zip(y.iter()).map(|(a, b)| (a ^ b).count_ones()).sum::<u32>();
+
+  // Roughly half of the 512 bits should differ.
+
+  assert!((192 ..= 320).contains(&d));
+}
+
+#[test]
+fn test_umulh() {
+  let reference = |x: u64, y: u64| ((x as u128 * y as u128) >> 64) as u64;
+  let edges = [0, 1, 2, 0xffff_ffff, 0x1_0000_0000, u64::MAX / 2, u64::MAX - 1, u64::MAX];
+
+  for &x in edges.iter() {
+    for &y in edges.iter() {
+      assert_eq!(wordmap::rng::umulh(x, y), reference(x, y));
+    }
+  }
+
+  assert_eq!(wordmap::rng::umulh(u64::MAX, u64::MAX), u64::MAX - 1);
+  assert_eq!(wordmap::rng::umulh(0, u64::MAX), 0);
+
+  let mut g = Rng::from_u64(1);
+
+  for _ in 0 .. 1000 {
+    let x = g.u64();
+    let y = g.u64();
+    assert_eq!(wordmap::rng::umulh(x, y), reference(x, y));
+  }
+
+  const X: u64 = wordmap::rng::umulh(1 << 63, 4);
+  assert_eq!(X, 2);
+}
+
+#[test]
+fn test_u64_below() {
+  let mut g = Rng::from_u64(2);
+  let mut a = [0u32; 6];
+
+  for _ in 0 .. 60_000 {
+    a[g.u64_below(6) as usize] += 1;
+  }
+
+  // Each count has mean 10_000 and standard deviation about 91.
+
+  assert!(a.iter().all(|n| (9_500 ..= 10_500).contains(n)));
+
+  for _ in 0 .. 1000 {
+    assert_eq!(g.u64_below(1), 0);
+    assert!(g.u64_below(u64::MAX) < u64::MAX);
+    assert!(g.u64_below((1 << 63) + 1) <= 1 << 63);
+  }
+}
+
+#[test]
+#[should_panic(expected = "bound must be nonzero")]
+fn test_u64_below_zero() {
+  let _ = Rng::from_u64(2).u64_below(0);
+}
+
+#[test]
+fn test_fill_bytes() {
+  let mut a = [0u8; 17];
+  let mut b = [0u8; 17];
+
+  Rng::from_u64(3).fill_bytes(&mut a);
+  Rng::from_u64(3).fill_bytes(&mut b);
+
+  assert_eq!(a, b);
+
+  let mut g = Rng::from_u64(3);
+  let x = g.u64().to_le_bytes();
+  let y = g.u64().to_le_bytes();
+  let z = g.u64().to_le_bytes();
+
+  assert_eq!(a[0 .. 8], x);
+  assert_eq!(a[8 .. 16], y);
+  assert_eq!(a[16], z[0]);
+
+  // Every byte is touched: over many fills, no position stays zero.
+
+  let mut g = Rng::from_u64(4);
+  let mut m = [0u8; 17];
+
+  for _ in 0 .. 64 {
+    let c = g.array_u8::<17>();
+    for i in 0 .. 17 { m[i] |= c[i]; }
+  }
+
+  assert!(m.iter().all(|&x| x != 0));
+
+  let mut e = [];
+  g.fill_bytes(&mut e);
+  assert_eq!(Rng::from_u64(3).array_u8::<17>(), a);
+}
+
+#[test]
+fn test_f64_and_bool() {
+  let mut g = Rng::from_u64(5);
+  let mut n = 0;
+  let mut s = 0.;
+
+  for _ in 0 .. 100_000 {
+    let x = g.f64();
+    assert!((0. .. 1.).contains(&x));
+    s += x;
+
+    let y = g.f64_range(-2., 3.);
+    assert!((-2. .. 3.).contains(&y));
+
+    n += g.bool() as u32;
+  }
+
+  assert!((49_000 ..= 51_000).contains(&n));
+  assert!((0.49 .. 0.51).contains(&(s / 100_000.)));
+  assert_eq!(g.f64_range(1., 1. + f64::EPSILON), 1.);
+}
+
+#[cfg(debug_assertions)]
+#[test]
+fn test_f64_range_empty() {
+  assert!(std::panic::catch_unwind(|| Rng::from_u64(1).f64_range(1., 1.)).is_err());
+  assert!(std::panic::catch_unwind(|| Rng::from_u64(1).f64_range(2., 1.)).is_err());
+  assert!(std::panic::catch_unwind(|| Rng::from_u64(1).f64_range(f64::NAN, 1.)).is_err());
+}
+
+#[test]
+fn test_shuffle_and_choose() {
+  let mut a = (0 .. 100).collect::<Vec<u32>>();
+  let mut b = a.clone();
+
+  Rng::from_u64(6).shuffle(&mut a);
+  Rng::from_u64(6).shuffle(&mut b);
+
+  assert_eq!(a, b);
+  assert_ne!(a, (0 .. 100).collect::<Vec<_>>());
+
+  a.sort();
+  assert_eq!(a, (0 .. 100).collect::<Vec<_>>());
+
+  let mut g = Rng::from_u64(7);
+  let mut e = [0u32; 0];
+  g.shuffle(&mut e);
+
+  assert_eq!(g.choose(&e), None);
+  assert_eq!(g.choose(&[5]), Some(&5));
+
+  let mut n = [0u32; 4];
+  for _ in 0 .. 4000 { n[*g.choose(&[0, 1, 2, 3]).unwrap()] += 1; }
+  assert!(n.iter().all(|&x| (850 ..= 1150).contains(&x)));
+}
+
+#[test]
+fn test_split() {
+  let stream = |g: &mut Rng| (0 .. 8).map(|_| g.u64()).collect::<Vec<_>>();
+
+  let mut g = Rng::from_u64(8);
+  let mut x = g.split();
+  let mut y = g.split();
+
+  let a = stream(&mut x);
+  let b = stream(&mut y);
+  let c = stream(&mut g);
+
+  assert_ne!(a, b);
+  assert_ne!(a, c);
+  assert_ne!(b, c);
+
+  let mut h = Rng::from_u64(8);
+
+  assert_eq!(stream(&mut h.split()), a);
+  assert_eq!(stream(&mut h.split()), b);
+  assert_eq!(stream(&mut h), c);
+}
+
+#[test]
+fn test_u32_u128_nonzero_u64() -> Result<(), std::fmt::Error> {
+  let mut s = String::new();
+  let mut g = Rng::from_u64(21);
+  let mut h = Rng::from_u64(21);
+
+  for _ in 0 .. 3 {
+    let x = g.u32();
+    assert_eq!(x as u64, h.u64() >> 32);
+    writeln!(s, "{:#010x} <- g.u32()", x)?;
+  }
+
+  for _ in 0 .. 3 {
+    let x = g.u128();
+    let a = h.u64();
+    let b = h.u64();
+    assert_eq!(x, a as u128 | (b as u128) << 64);
+    writeln!(s, "{:#034x} <- g.u128()", x)?;
+  }
+
+  for _ in 0 .. 3 {
+    let x = g.nonzero_u64();
+    assert_eq!(x.get(), h.u64());
+    writeln!(s, "{:#018x} <- g.nonzero_u64()", x)?;
+  }
+
+  expect![[r#"
+      0x701ab166 <- g.u32()
+      0x9d4d7435 <- g.u32()
+      0x70f3b53f <- g.u32()
+      0xf119dae749641537da8b5034765322dc <- g.u128()
+      0x335b3b44c05ccc507295fe5fb3e5ce69 <- g.u128()
+      0x886994bbbddb3c7e2f1f69989b2aa5dc <- g.u128()
+      0xffd346277cb40013 <- g.nonzero_u64()
+      0xaffb08edd590da87 <- g.nonzero_u64()
+      0xe4d04c737bd3e3b6 <- g.nonzero_u64()
+  "#]].assert_eq(&s);
+
+  Ok(())
+}
+
+#[cfg(feature = "rand_core")]
+#[test]
+fn test_rand_core() {
+  use rand::distributions::Distribution;
+  use rand::distributions::Uniform;
+  use rand_core::RngCore;
+  use rand_core::SeedableRng;
+
+  let mut g = <Rng as SeedableRng>::from_seed([9; 16]);
+  let mut h = Rng::from_seed([9; 16]);
+
+  assert_eq!(g.next_u64(), h.u64());
+  assert_eq!(g.next_u32() as u64, h.u64() >> 32);
+
+  let mut a = [0; 12];
+  RngCore::fill_bytes(&mut g, &mut a);
+  assert_eq!(a, h.array_u8::<12>());
+
+  let d = Uniform::new(10, 20);
+  let mut n = [0u32; 10];
+
+  for _ in 0 .. 10_000 {
+    let x: u32 = d.sample(&mut g);
+    assert!((10 .. 20).contains(&x));
+    n[x as usize - 10] += 1;
+  }
+
+  assert!(n.iter().all(|&x| (850 ..= 1150).contains(&x)));
+  assert_eq!(<Rng as SeedableRng>::from_seed([0; 16]).state().get(), 1);
+}
@@ -0,0 +1,37 @@
+use crate::prelude::*;
+
+#[test]
+fn test_serde_map() {
+  let mut t = HashMapNZ64::<String>::new();
+
+  for i in [3, 1, 2] {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i.to_string());
+  }
+
+  let s = serde_json::to_string(&t).unwrap();
+
+  assert_eq!(s, r#"{"1":"1","2":"2","3":"3"}"#);
+
+  let u = serde_json::from_str::<HashMapNZ64<String>>(&s).unwrap();
+
+  assert_eq!(u, t);
+  assert!(serde_json::from_str::<HashMapNZ64<String>>(r#"{"0":"0"}"#).is_err());
+}
+
+#[test]
+fn test_serde_set() {
+  let mut s = HashSetNZ64::new();
+
+  for i in 1 ..= 100 {
+    let _ = s.insert(NonZeroU64::new(i).unwrap());
+  }
+
+  let a = serde_json::to_string(&s).unwrap();
+
+  assert_eq!(a, serde_json::to_string(&(1 ..= 100).collect::<Vec<_>>()).unwrap());
+
+  let u = serde_json::from_str::<HashSetNZ64>(&a).unwrap();
+
+  assert_eq!(u.len(), 100);
+  assert!((1 ..= 100).all(|i| u.contains(NonZeroU64::new(i).unwrap())));
+}
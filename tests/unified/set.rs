@@ -0,0 +1,192 @@
+use crate::prelude::*;
+
+#[test]
+fn test_insert_new() {
+  let mut s = HashSetNZ64::new();
+  let mut out = Vec::new();
+  let keys = |a: &[u64]| a.iter().map(|&i| NonZeroU64::new(i).unwrap()).collect::<Vec<_>>();
+
+  s.insert_new(&keys(&[1, 2, 3]), &mut out);
+  assert_eq!(out, keys(&[1, 2, 3]));
+
+  out.clear();
+  s.insert_new(&keys(&[2, 4, 4, 5, 1, 6]), &mut out);
+  assert_eq!(out, keys(&[4, 5, 6]));
+  assert_eq!(s.len(), 6);
+}
+
+#[test]
+fn test_new_with_mixer() {
+  let m = Mixer::new([1, 2]);
+  let mut s = HashSetNZ64::new_with_mixer(m);
+  let mut t = HashSetNZ64::new_with_mixer(m);
+
+  assert_eq!(s.mixer(), m);
+
+  for i in 1 ..= 100 {
+    let _ = s.insert(NonZeroU64::new(i).unwrap());
+    let _ = t.insert(NonZeroU64::new(101 - i).unwrap());
+  }
+
+  assert_eq!(s.iter().collect::<Vec<_>>(), t.iter().collect::<Vec<_>>());
+}
+
+#[test]
+fn test_set_algebra() {
+  let set = |a: std::ops::RangeInclusive<u64>| {
+    let mut s = HashSetNZ64::new();
+    for i in a { let _ = s.insert(NonZeroU64::new(i).unwrap()); }
+    s
+  };
+
+  let sorted = |s: &HashSetNZ64| {
+    let mut a = s.iter().map(|x| x.get()).collect::<Vec<_>>();
+    a.sort();
+    a
+  };
+
+  // Disjoint.
+
+  let a = set(1 ..= 10);
+  let b = set(11 ..= 15);
+
+  assert_eq!(sorted(&a.union(&b)), (1 ..= 15).collect::<Vec<_>>());
+  assert_eq!(a.intersection(&b).len(), 0);
+  assert_eq!(sorted(&a.difference(&b)), sorted(&a));
+  assert_eq!(a.symmetric_difference(&b).len(), 15);
+
+  // Identical.
+
+  let c = set(1 ..= 10);
+
+  assert_eq!(sorted(&a.union(&c)), sorted(&a));
+  assert_eq!(sorted(&a.intersection(&c)), sorted(&a));
+  assert_eq!(a.difference(&c).len(), 0);
+  assert_eq!(a.symmetric_difference(&c).len(), 0);
+
+  // Partial overlap.
+
+  let d = set(6 ..= 20);
+
+  assert_eq!(a.union(&d).len(), 20);
+  assert_eq!(sorted(&a.intersection(&d)), (6 ..= 10).collect::<Vec<_>>());
+  assert_eq!(sorted(&d.intersection(&a)), (6 ..= 10).collect::<Vec<_>>());
+  assert_eq!(sorted(&a.difference(&d)), (1 ..= 5).collect::<Vec<_>>());
+  assert_eq!(sorted(&d.difference(&a)), (11 ..= 20).collect::<Vec<_>>());
+  assert_eq!(sorted(&a.symmetric_difference(&d)), (1 ..= 5).chain(11 ..= 20).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_set_predicates() {
+  let set = |a: &[u64]| {
+    let mut s = HashSetNZ64::new();
+    for &i in a { let _ = s.insert(NonZeroU64::new(i).unwrap()); }
+    s
+  };
+
+  let e = set(&[]);
+  let a = set(&[1, 2, 3]);
+  let b = set(&[1, 2, 3, 4]);
+  let c = set(&[4, 5]);
+
+  assert!(e.is_subset(&e) && e.is_subset(&a));
+  assert!(e.is_disjoint(&e) && e.is_disjoint(&a) && a.is_disjoint(&e));
+  assert!(a.is_superset(&e) && ! e.is_superset(&a));
+
+  assert!(a.is_subset(&a) && a.is_superset(&a));
+  assert!(a.is_subset(&b) && ! b.is_subset(&a));
+  assert!(b.is_superset(&a) && ! a.is_superset(&b));
+  assert!(! a.is_subset(&c));
+
+  assert!(a.is_disjoint(&c) && c.is_disjoint(&a));
+  assert!(! b.is_disjoint(&c) && ! c.is_disjoint(&b));
+}
+
+#[test]
+fn test_set_operators() {
+  let k = |i: u64| NonZeroU64::new(i).unwrap();
+  let a = HashSetNZ64::from([k(1), k(2), k(3)]);
+  let b = HashSetNZ64::from([k(3), k(4)]);
+
+  let sorted = |s: HashSetNZ64| {
+    let mut a = s.iter().map(|x| x.get()).collect::<Vec<_>>();
+    a.sort();
+    a
+  };
+
+  assert_eq!(sorted(&a | &b), [1, 2, 3, 4]);
+  assert_eq!(sorted(&a & &b), [3]);
+  assert_eq!(sorted(&a - &b), [1, 2]);
+  assert_eq!(sorted(&b - &a), [4]);
+  assert_eq!(sorted(&a ^ &b), [1, 2, 4]);
+}
+
+#[test]
+fn test_set_clone_eq() {
+  let mut a = HashSetNZ64::new();
+  let mut b = HashSetNZ64::new();
+
+  for i in 1 ..= 100 {
+    let _ = a.insert(NonZeroU64::new(i).unwrap());
+    let _ = b.insert(NonZeroU64::new(101 - i).unwrap());
+  }
+
+  let c = a.clone();
+
+  assert_eq!(c.len(), 100);
+  assert_eq!(c, a);
+  assert_eq!(a, b);
+
+  let _ = b.remove(NonZeroU64::new(50).unwrap());
+  assert_ne!(a, b);
+
+  let _ = b.insert(NonZeroU64::new(500).unwrap());
+  assert_ne!(a, b);
+}
+
+#[test]
+fn test_set_from_iter() {
+  let k = |i: u64| NonZeroU64::new(i).unwrap();
+  let mut s = (1 ..= 1000).map(k).collect::<HashSetNZ64>();
+
+  assert_eq!(s.len(), 1000);
+  assert!(s.contains(k(1)) && s.contains(k(1000)) && ! s.contains(k(1001)));
+
+  s.extend((501 ..= 1500).map(k));
+
+  assert_eq!(s.len(), 1500);
+  assert!(s.contains(k(1500)));
+
+  let mut a = s.into_iter().map(|x| x.get()).collect::<Vec<_>>();
+  a.sort();
+
+  assert_eq!(a, (1 ..= 1500).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_contains_all_and_retain_contained() {
+  let k = |i: u64| NonZeroU64::new(i).unwrap();
+  let keys = (1 ..= 100).map(k).collect::<Vec<_>>();
+
+  let s = HashSetNZ64::new();
+  let mut a = keys.clone();
+
+  assert!(! s.contains_all(&keys));
+  assert!(s.contains_all(&[]));
+  s.retain_contained(&mut a);
+  assert!(a.is_empty());
+
+  let s = keys.iter().copied().collect::<HashSetNZ64>();
+  let mut a = keys.clone();
+
+  assert!(s.contains_all(&keys));
+  assert!(s.contains_all(&[]));
+  s.retain_contained(&mut a);
+  assert_eq!(a, keys);
+
+  let mut a = (50 ..= 150).rev().map(k).collect::<Vec<_>>();
+
+  assert!(! s.contains_all(&a));
+  s.retain_contained(&mut a);
+  assert_eq!(a, (50 ..= 100).rev().map(k).collect::<Vec<_>>());
+}
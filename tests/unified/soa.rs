@@ -0,0 +1,160 @@
+use crate::prelude::*;
+
+#[test]
+fn test_matches_map() {
+  let mut g = Rng::from_u64(1);
+  let m = Mixer::new_seeded(&mut g);
+  let mut t = HashMapNZ64::<u64>::new_with_mixer(m);
+  let mut u = SoaMapNZ64::<u64>::new_with_mixer(m);
+
+  assert!(u.is_empty());
+  assert_eq!(u.get(NonZeroU64::new(1).unwrap()), None);
+  assert_eq!(u.remove(NonZeroU64::new(1).unwrap()), None);
+
+  for _ in 0 .. 100_000 {
+    let k = NonZeroU64::new(1 + g.bounded_u32(9_999) as u64).unwrap();
+    let v = g.u64();
+
+    match g.bounded_u32(3) {
+      0 | 1 => assert_eq!(u.insert(k, v), t.insert(k, v)),
+      2 => assert_eq!(u.remove(k), t.remove(k)),
+      _ => { *u.get_mut(k).unwrap_or(&mut 0) += 1; *t.get_mut(k).unwrap_or(&mut 0) += 1; }
+    }
+
+    assert_eq!(u.len(), t.len());
+  }
+
+  for i in 1 ..= 10_000 {
+    let k = NonZeroU64::new(i).unwrap();
+    assert_eq!(u.get(k), t.get(k));
+    assert_eq!(u.contains_key(k), t.contains_key(k));
+  }
+
+  assert_eq!(u.iter().len(), t.len());
+  assert_eq!(u.iter().collect::<Vec<_>>(), t.iter().collect::<Vec<_>>());
+  assert_eq!(format!("{:?}", u), format!("{:?}", t));
+
+  u.clear();
+  assert!(u.is_empty());
+  assert_eq!(u.iter().count(), 0);
+  let _ = u.insert(NonZeroU64::new(1).unwrap(), 1);
+  assert_eq!(u.get(NonZeroU64::new(1).unwrap()), Some(&1));
+}
+
+#[test]
+fn test_drop_and_zero_sized() {
+  let mut g = Rng::from_u64(2);
+  let mut t = SoaMapNZ64::<String>::new_seeded(&mut g);
+
+  for i in 1 ..= 1000 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i.to_string());
+  }
+
+  for i in 1 ..= 500 {
+    assert_eq!(t.remove(NonZeroU64::new(2 * i).unwrap()), Some((2 * i).to_string()));
+  }
+
+  assert_eq!(t.get(NonZeroU64::new(999).unwrap()).map(|x| x.as_str()), Some("999"));
+
+  t.clear();
+  t.reset();
+  let _ = t.insert(NonZeroU64::new(1).unwrap(), "1".to_string());
+
+  let mut u = SoaMapNZ64::<()>::new_seeded(&mut g);
+
+  for i in 1 ..= 1000 {
+    assert_eq!(u.insert(NonZeroU64::new(i).unwrap(), ()), None);
+  }
+
+  assert_eq!(u.len(), 1000);
+  assert_eq!(u.insert(NonZeroU64::new(7).unwrap(), ()), Some(()));
+}
+
+#[test]
+fn test_probe_bytes() {
+  // Probes only read the hash array, so their cost doesn't depend on the size
+  // of the values. A probe in a `HashMapNZ64` with the same mixer visits the
+  // same slots, but reads whole slots, which are at least twice as large.
+
+  let m = Mixer::new_seeded(&mut Rng::from_u64(3));
+  let mut t = SoaMapNZ64::<u64>::new_with_mixer(m);
+  let mut u = SoaMapNZ64::<[u64; 16]>::new_with_mixer(m);
+  let mut v = HashMapNZ64::<u64>::new_with_mixer(m);
+
+  for i in 1 ..= 1000 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), i);
+    let _ = u.insert(NonZeroU64::new(i).unwrap(), [i; 16]);
+    let _ = v.insert(NonZeroU64::new(i).unwrap(), i);
+  }
+
+  // The extra slots are fewer than the regular ones, so the number of regular
+  // slots is the largest power of two in the total.
+
+  let a = crate::map::raw_slots(&v);
+  let d: usize = 1 << a.len().ilog2();
+  let s = 64 - d.ilog2() as usize;
+
+  let mut n = 0;
+
+  for i in 1 ..= 2000 {
+    let k = NonZeroU64::new(i).unwrap();
+    let h = m.hash(k).get();
+    let x = soa::internal::probe_bytes(&t, k);
+    assert_eq!(x, soa::internal::probe_bytes(&u, k));
+    assert_eq!(x % 8, 0);
+
+    let mut j = d - 1 - map::internal::spot(s, h) as usize;
+    let mut y = 16;
+
+    while a[j][0] > h {
+      j += 1;
+      y += 16;
+    }
+
+    assert_eq!(2 * x, y);
+    n += x;
+  }
+
+  assert!(n < 2000 * 8 * 8);
+}
+
+#[test]
+fn test_default() {
+  let t = SoaMapNZ64::<u64>::default();
+  assert!(t.is_empty());
+}
+
+#[test]
+fn test_clear_panicking_drop() {
+  use std::panic::AssertUnwindSafe;
+
+  struct Bomb(u64);
+
+  impl Drop for Bomb {
+    fn drop(&mut self) {
+      if self.0 == 50 { panic!("boom") }
+    }
+  }
+
+  let mut t = SoaMapNZ64::new();
+
+  for i in 1 ..= 100 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), Bomb(i));
+  }
+
+  let r = std::panic::catch_unwind(AssertUnwindSafe(|| t.clear()));
+
+  assert!(r.is_err());
+  assert!(t.is_empty());
+  assert_eq!(t.iter().count(), 0);
+
+  for i in 1 ..= 100 {
+    assert!(t.get(NonZeroU64::new(i).unwrap()).is_none());
+  }
+
+  for i in 101 ..= 200 {
+    let _ = t.insert(NonZeroU64::new(i).unwrap(), Bomb(i));
+  }
+
+  assert_eq!(t.len(), 100);
+}